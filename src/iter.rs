@@ -1,13 +1,15 @@
 //! Iterator types for the [`Pool`]
 
+use std::iter::FromIterator;
+
 use super::*;
 
-pub struct Iter<'a, T: 'static> {
+pub struct Iter<'a, T> {
     // TODO: len: u32,
     pub(crate) entries: slice::Iter<'a, PoolEntry<T>>,
 }
 
-impl<'a, T: 'static> Iterator for Iter<'a, T> {
+impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -21,12 +23,12 @@ impl<'a, T: 'static> Iterator for Iter<'a, T> {
     }
 }
 
-pub struct IterMut<'a, T: 'static> {
+pub struct IterMut<'a, T> {
     // TODO: len: u32,
     pub(crate) entries: slice::IterMut<'a, PoolEntry<T>>,
 }
 
-impl<'a, T: 'static> Iterator for IterMut<'a, T> {
+impl<'a, T> Iterator for IterMut<'a, T> {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -40,7 +42,7 @@ impl<'a, T: 'static> Iterator for IterMut<'a, T> {
     }
 }
 
-impl<'a, T: 'static> IntoIterator for &'a Pool<T> {
+impl<'a, T, A: Allocator> IntoIterator for &'a Pool<T, A> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T>;
     fn into_iter(self) -> Self::IntoIter {
@@ -50,7 +52,7 @@ impl<'a, T: 'static> IntoIterator for &'a Pool<T> {
     }
 }
 
-impl<'a, T: 'static> IntoIterator for &'a mut Pool<T> {
+impl<'a, T, A: Allocator> IntoIterator for &'a mut Pool<T, A> {
     type Item = &'a mut T;
     type IntoIter = IterMut<'a, T>;
     fn into_iter(self) -> Self::IntoIter {
@@ -59,3 +61,89 @@ impl<'a, T: 'static> IntoIterator for &'a mut Pool<T> {
         }
     }
 }
+
+/// Collects items into slot-addressed entries without creating handles
+///
+/// The items have zero reference count, so they're not invalidated on sync.
+impl<T> FromIterator<T> for Pool<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut pool = Pool::with_capacity(iter.size_hint().0);
+        for item in iter {
+            pool.insert(item, 0).expect("Generation overflow!");
+        }
+        pool
+    }
+}
+
+/// Mutable cursor over live items of a [`Pool`] in slot order. Created with [`Pool::cursor_mut`]
+///
+/// Unlike [`IterMut`], it can remove the current item or insert new items while traversing.
+#[derive(Debug)]
+pub struct Cursor<'a, T, A: Allocator = Global> {
+    pub(crate) pool: &'a mut Pool<T, A>,
+    /// Index of the current entry, or `entries.len()` when exhausted
+    pub(crate) pos: usize,
+}
+
+impl<'a, T, A: Allocator> Cursor<'a, T, A> {
+    pub(crate) fn new(pool: &'a mut Pool<T, A>) -> Self {
+        let mut cursor = Self { pool, pos: 0 };
+        cursor.seek_live();
+        cursor
+    }
+
+    /// Moves to the first live entry at or after the current position
+    fn seek_live(&mut self) {
+        let entries = &self.pool.entries;
+        while self.pos < entries.len() && entries[self.pos].data.is_none() {
+            self.pos += 1;
+        }
+    }
+
+    /// Returns the current item, or `None` if the cursor is exhausted
+    pub fn current(&mut self) -> Option<(Slot, &mut T)> {
+        let data = self.pool.entries.get_mut(self.pos)?.data.as_mut()?;
+        Some((Slot(self.pos as u32), data))
+    }
+
+    /// Moves to the next live item
+    pub fn advance(&mut self) {
+        if self.pos < self.pool.entries.len() {
+            self.pos += 1;
+            self.seek_live();
+        }
+    }
+
+    /// Removes the current item and moves to the next one
+    ///
+    /// The slot gets a new generation, so handles to the removed item are rejected.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let item = self.pool.take_data(self.pos)?;
+        self.advance();
+        Some(item)
+    }
+
+    /// Inserts the item into the first vacant slot after the current one (or a new entry), so that
+    /// it's visited later in this traversal unless the cursor is exhausted. Returns a strong
+    /// [`Handle`] for it
+    pub fn insert_after(&mut self, item: T) -> Handle<T> {
+        let entries = &self.pool.entries;
+        let i = (self.pos + 1..entries.len())
+            .find(|&i| entries[i].data.is_none() && !entries[i].is_retired())
+            .unwrap_or(entries.len());
+
+        let gen = self.pool.put_at(i, item, 1);
+        if i == self.pos {
+            // pushed at the end of an exhausted cursor, which stays exhausted
+            self.pos += 1;
+        }
+
+        Handle {
+            slot: Slot(i as u32),
+            gen,
+            sender: self.pool.tx.clone(),
+            _ty: PhantomData,
+        }
+    }
+}
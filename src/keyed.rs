@@ -0,0 +1,88 @@
+//! [`KeyedPool`], a [`Pool`] with a side index from external keys to items
+
+use std::hash::Hash;
+
+use super::*;
+
+/// [`Pool`] with items looked up by arbitrary keys (e.g. names)
+///
+/// The index holds [`WeakHandle`]s, so it doesn't keep items alive. Keys of invalidated items are
+/// stale until they're respawned or pruned.
+#[derive(Debug)]
+pub struct KeyedPool<K, T> {
+    pool: Pool<T>,
+    keys: HashMap<K, WeakHandle<T>>,
+}
+
+impl<K: Eq + Hash, T> Default for KeyedPool<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash, T> KeyedPool<K, T> {
+    pub fn new() -> Self {
+        Self {
+            pool: Pool::with_capacity(0),
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool<T> {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut Pool<T> {
+        &mut self.pool
+    }
+
+    /// Returns a strong [`Handle`] to the live item for the key, or spawns a new one
+    ///
+    /// If the key is known, the pool is synced first with [`Pool::sync_refcounts_and_invalidate`],
+    /// so that an item whose handles were all dropped is respawned instead of being returned with a
+    /// handle that dangles after the next sync. An item kept with zero reference count
+    /// ([`OnZeroPolicy::KeepUntilManual`]) is returned with a new handle. A stale entry for the key
+    /// is replaced.
+    pub fn get_or_spawn(&mut self, key: K, f: impl FnOnce() -> T) -> Handle<T> {
+        if let Some(&weak) = self.keys.get(&key) {
+            self.pool.sync_refcounts_and_invalidate();
+            if self.pool.get(&weak).is_some() {
+                // unlike `Pool::upgrade`, this accepts zero reference count. Count the new handle
+                // right away like `Pool::add`
+                self.pool.entries[weak.slot.to_usize()].ref_count += 1;
+                return Handle {
+                    slot: weak.slot,
+                    gen: weak.gen,
+                    sender: self.pool.tx.clone(),
+                    _ty: PhantomData,
+                };
+            }
+        }
+
+        let handle = self.pool.add(f());
+        self.keys.insert(key, handle.to_downgraded());
+        handle
+    }
+
+    /// Returns the live item for the key
+    pub fn get(&self, key: &K) -> Option<&T> {
+        self.keys.get(key).and_then(|w| self.pool.get(w))
+    }
+
+    /// Returns the live item for the key
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut T> {
+        let weak = self.keys.get(key)?;
+        self.pool.get_mut(weak)
+    }
+
+    /// Removes keys of invalidated items
+    pub fn prune(&mut self) {
+        let pool = &self.pool;
+        self.keys.retain(|_, w| pool.get(w).is_some());
+    }
+
+    /// Number of keys, including stale ones
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+}
@@ -8,45 +8,216 @@ Note that the pool does NOT drop unreferenced items until it's synced. Also it's
 for no particular reason.
 */
 
-// TODO: add length tracking and implement FuseIterator for iterator types
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+// TODO: implement FuseIterator for iterator types
 
 pub mod iter;
+pub mod keyed;
 pub mod smpsc;
 pub mod tree;
 
+#[cfg(feature = "sparse")]
+pub mod sparse;
+
 #[cfg(test)]
 mod test;
 
 #[cfg(feature = "igri")]
 use igri::Inspect;
 
-use std::{cmp, marker::PhantomData, ops, slice};
+use std::{
+    cmp,
+    collections::{HashMap, HashSet, TryReserveError},
+    error, fmt,
+    marker::PhantomData,
+    mem, ops, slice,
+};
 
 use derivative::Derivative;
 
 use crate::smpsc::{Receiver, Sender};
 
+#[cfg(feature = "allocator_api")]
+pub use std::alloc::{Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Stand-in for `std::alloc::Allocator`, implemented only by [`Global`]. Enable the
+/// `allocator_api` feature (nightly) to store [`Pool`] entries in a custom allocator
+#[cfg(not(feature = "allocator_api"))]
+#[doc(hidden)]
+pub trait Allocator: sealed::Sealed {}
+
+/// Stand-in for `std::alloc::Global`, the default allocator of [`Pool`] entries
+#[cfg(not(feature = "allocator_api"))]
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+#[cfg(not(feature = "allocator_api"))]
+impl sealed::Sealed for Global {}
+
+#[cfg(not(feature = "allocator_api"))]
+impl Allocator for Global {}
+
 type Gen = std::num::NonZeroU32;
 
+/// See [`Pool::set_invalidate_hook`]
+type InvalidateHook<T> = Box<dyn FnMut(Slot, &T)>;
+
+/// Receives slot lifecycle events of a [`Pool`]. See [`Pool::with_observer`]
+pub trait PoolObserver<T> {
+    /// Called when a vacant slot gets an item, with the new generation of the slot. Pushing a new
+    /// entry is not a reuse
+    fn on_reuse(&mut self, _slot: Slot, _gen: u32) {}
+    /// Called with each item just before it's removed, after the invalidation hook. See
+    /// [`Pool::set_invalidate_hook`] for the methods that report removals
+    fn on_invalidate(&mut self, _slot: Slot, _item: &T) {}
+}
+
 /// Type for reference counting
 pub type RefCount = u16;
 
 /// Newtype of `u32`
+///
+/// It's `#[repr(transparent)]`, so it has the same ABI as `u32` and can cross FFI boundaries.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "igri", derive(Inspect))]
+#[repr(transparent)]
 pub struct Slot(u32);
 
+/// Formats as the numeric value
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
 impl Slot {
+    /// Sentinel slot that never refers to an item
+    pub const INVALID: Slot = Slot(u32::MAX);
+
     pub fn to_usize(&self) -> usize {
         self.0 as usize
     }
+
+    /// Returns `None` on overflow
+    pub fn checked_add(self, n: u32) -> Option<Slot> {
+        self.0.checked_add(n).map(Slot)
+    }
+
+    /// Returns `None` on underflow
+    pub fn checked_sub(self, n: u32) -> Option<Slot> {
+        self.0.checked_sub(n).map(Slot)
+    }
+
+    pub fn saturating_add(self, n: u32) -> Slot {
+        Slot(self.0.saturating_add(n))
+    }
+
+    pub fn saturating_sub(self, n: u32) -> Slot {
+        Slot(self.0.saturating_sub(n))
+    }
+}
+
+/// Panics on overflow
+impl ops::Add<u32> for Slot {
+    type Output = Slot;
+    fn add(self, n: u32) -> Slot {
+        self.checked_add(n).expect("Slot overflow!")
+    }
+}
+
+/// Panics on underflow
+impl ops::Sub<u32> for Slot {
+    type Output = Slot;
+    fn sub(self, n: u32) -> Slot {
+        self.checked_sub(n).expect("Slot underflow!")
+    }
+}
+
+/// Anything that refers to a [`Slot`]
+///
+/// Slot-based accessors take `impl AsSlot`, so any handle type can be passed where only the slot
+/// matters. Generations are NOT checked through this trait.
+pub trait AsSlot {
+    fn as_slot(&self) -> Slot;
+}
+
+impl AsSlot for Slot {
+    fn as_slot(&self) -> Slot {
+        *self
+    }
+}
+
+impl<S: AsSlot + ?Sized> AsSlot for &S {
+    fn as_slot(&self) -> Slot {
+        (**self).as_slot()
+    }
 }
 
+/// Error returned by the fallible (`try_`) methods of [`Pool`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolError {
+    /// The generation of a reused slot can't be advanced any more
+    GenerationOverflow,
+    /// The slot is not in the pool
+    SlotOutOfBounds,
+    /// The slot has no item
+    VacantSlot,
+    /// The slot has been reused by another item
+    StaleGeneration,
+    /// The same slot was given where distinct slots are required
+    DuplicateSlot,
+    /// The slot already has an item
+    OccupiedSlot,
+    /// A drop message was received for an item with zero reference count
+    RefCountUnderflow,
+    /// The item is still referred to by strong handles
+    Referenced,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::GenerationOverflow => "generation overflow",
+            Self::SlotOutOfBounds => "slot out of bounds",
+            Self::VacantSlot => "vacant slot",
+            Self::StaleGeneration => "stale generation",
+            Self::DuplicateSlot => "duplicate slot",
+            Self::OccupiedSlot => "occupied slot",
+            Self::RefCountUnderflow => "reference count underflow",
+            Self::Referenced => "item is still referenced",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl error::Error for PoolError {}
+
 /// Reference counting message (New | Drop)
+///
+/// Messages are tagged with the generation of the handle, so that messages from handles to a
+/// previous item in a reused slot are ignored.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum Message {
-    New(Slot),
-    Drop(Slot),
+    New(Slot, Gen),
+    Drop(Slot, Gen),
+}
+
+/// Where messages from handles to an item moved out of a [`Pool`] are forwarded. See
+/// [`Pool::move_into`]
+#[derive(Debug)]
+struct Redirect {
+    tx: Sender<Message>,
+    slot: Slot,
+    gen: Gen,
+    /// Number of handles still reporting to the source pool
+    count: RefCount,
 }
 
 /// Owing index to an item in a [`Pool`]
@@ -93,11 +264,49 @@ impl<T> Handle<T> {
     pub fn to_downgraded(&self) -> WeakHandle<T> {
         self.clone().downgrade()
     }
+
+    /// Reinterprets the item type, keeping the slot, generation and reference count
+    ///
+    /// # Safety
+    ///
+    /// **This bypasses type safety.** The handle keeps reporting to the channel of its original
+    /// pool. Only use it on a handle cast from `Handle<U>` in the first place (e.g. to round-trip
+    /// through type-erased storage), and only use it with the pool it was created by.
+    pub unsafe fn cast<U>(self) -> Handle<U> {
+        let this = mem::ManuallyDrop::new(self);
+        Handle {
+            slot: this.slot,
+            gen: this.gen,
+            // moved out; `Handle::drop` is not run for `this`
+            sender: std::ptr::read(&this.sender),
+            _ty: PhantomData,
+        }
+    }
+
+    /// Converts to a weak handle without sending `Drop`, leaking one reference count
+    ///
+    /// The item is never invalidated on sync; remove it with [`Pool::force_invalidate`].
+    pub fn leak(self) -> WeakHandle<T> {
+        let this = mem::ManuallyDrop::new(self);
+        // release the channel without running `Handle::drop`
+        drop(unsafe { std::ptr::read(&this.sender) });
+        WeakHandle {
+            slot: this.slot,
+            gen: this.gen,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> AsSlot for Handle<T> {
+    fn as_slot(&self) -> Slot {
+        self.slot
+    }
 }
 
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Self {
-        self.sender.send(Message::New(self.slot));
+        self.sender.send(Message::New(self.slot, self.gen));
 
         Self {
             slot: self.slot,
@@ -110,7 +319,7 @@ impl<T> Clone for Handle<T> {
 
 impl<T> Drop for Handle<T> {
     fn drop(&mut self) {
-        self.sender.send(Message::Drop(self.slot));
+        self.sender.send(Message::Drop(self.slot, self.gen));
     }
 }
 
@@ -141,6 +350,25 @@ impl<T> WeakHandle<T> {
     pub fn slot(&self) -> Slot {
         self.slot
     }
+
+    /// Reinterprets the item type, keeping the slot and generation
+    ///
+    /// **Beware**: nothing checks that the handle is used with a pool of the new type that it
+    /// belongs to. A cast handle used with another pool resolves to an unrelated item if the slot
+    /// and generation happen to match. Cast back before use (e.g. after type-erased storage).
+    pub fn cast<U>(self) -> WeakHandle<U> {
+        WeakHandle {
+            slot: self.slot,
+            gen: self.gen,
+            _ty: PhantomData,
+        }
+    }
+}
+
+impl<T> AsSlot for WeakHandle<T> {
+    fn as_slot(&self) -> Slot {
+        self.slot
+    }
 }
 
 impl<T> From<Handle<T>> for WeakHandle<T> {
@@ -162,28 +390,167 @@ pub(crate) struct PoolEntry<T> {
     ref_count: RefCount,
 }
 
+impl<T> PoolEntry<T> {
+    fn next_gen(&self) -> Option<Gen> {
+        self.gen.get().checked_add(1).and_then(Gen::new)
+    }
+
+    /// Advances the generation so that handles to the previous item are rejected
+    fn bump_gen(&mut self) {
+        self.gen = self.next_gen().expect("Generation overflow!");
+    }
+
+    /// Advances the generation of a vacated entry. An exhausted generation is kept, which retires
+    /// the slot: it's never filled again, so handles to the previous item stay rejected
+    fn vacate_gen(&mut self) {
+        if let Some(gen) = self.next_gen() {
+            self.gen = gen;
+        }
+    }
+
+    /// If it's vacant with an exhausted generation
+    fn is_retired(&self) -> bool {
+        self.data.is_none() && self.next_gen().is_none()
+    }
+}
+
+/// Metadata of an entry in a [`Pool`], without reference to the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DebugEntry {
+    pub slot: Slot,
+    pub gen: u32,
+    /// If the slot has an item
+    pub present: bool,
+    /// Reference count as of the last synchronization
+    pub ref_count: RefCount,
+}
+
+/// Public mirror of an entry in a [`Pool`]. See [`Pool::into_parts`] and [`Pool::from_parts`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolEntrySnapshot<T> {
+    pub data: Option<T>,
+    /// Generation of the slot. Zero is treated as one
+    pub gen: u32,
+    pub ref_count: RefCount,
+}
+
+/// What [`Pool::sync_refcounts_and_invalidate`] does with items whose reference count reaches zero
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OnZeroPolicy {
+    /// Drop them on sync
+    #[default]
+    Invalidate,
+    /// Keep them until [`Pool::invalidate_unreferenced`] or [`Pool::try_invalidate`] is called
+    KeepUntilManual,
+}
+
+/// What [`Pool::add`] does when the first vacant slot has an exhausted generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GenerationOverflowPolicy {
+    /// Fail with [`PoolError::GenerationOverflow`] ([`Pool::add`] panics)
+    #[default]
+    Error,
+    /// Skip the slot. Slots with exhausted generations are never handed out again
+    Retire,
+}
+
+/// Behavior settings of a [`Pool`]. See [`PoolBuilder`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PoolConfig {
+    pub on_zero_policy: OnZeroPolicy,
+    /// If set, [`Pool::sync_refcounts_and_invalidate`] and [`Pool::sweep`] call
+    /// [`Pool::shrink_to_fit`] when [`Pool::fragmentation`] exceeds it
+    pub auto_shrink_fraction: Option<f32>,
+    pub generation_overflow: GenerationOverflowPolicy,
+}
+
+/// Saved state of a [`Pool`]. See [`Pool::snapshot`] and [`Pool::restore`]
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot<T> {
+    entries: Vec<PoolEntry<T>>,
+    new_entry_gen: Gen,
+}
+
+/// Items invalidated by [`Pool::collect_garbage`]. They're dropped when the batch is dropped
+#[derive(Debug)]
+pub struct GarbageBatch<T> {
+    items: Vec<(Slot, T)>,
+}
+
+impl<T> GarbageBatch<T> {
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an iterator of `(former slot, item)`
+    pub fn iter(&self) -> impl Iterator<Item = (Slot, &T)> {
+        self.items.iter().map(|(slot, item)| (*slot, item))
+    }
+
+    pub fn into_vec(self) -> Vec<(Slot, T)> {
+        self.items
+    }
+}
+
 /// Dynamic array with reference-counted [`Handle`]s
 ///
-/// Be sure to call message syncing method to track reference counts.
-#[derive(Debug)]
+/// Be sure to call message syncing method to track reference counts. With the `allocator_api`
+/// feature (nightly), the entries can be stored in a custom allocator (see `Pool::new_in`).
+#[derive(Derivative)]
+#[derivative(Debug(bound = "T: fmt::Debug"))]
 #[cfg_attr(
     feature = "igri",
     derive(Inspect),
     inspect(with = "inspect_pool", bounds = "T: Inspect")
 )]
-pub struct Pool<T> {
+pub struct Pool<T, A: Allocator = Global> {
     /// NOTE: we never call [`Vec::remove`]; it aligns (change positions of) other items.
+    #[cfg(feature = "allocator_api")]
+    entries: Vec<PoolEntry<T>, A>,
+    #[cfg(not(feature = "allocator_api"))]
     entries: Vec<PoolEntry<T>>,
+    /// Number of live items
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    n_live: usize,
     /// Receiver
     #[cfg_attr(feature = "igri", inspect(skip))]
     rx: Receiver<Message>,
     /// Sender. Cloned and passed to [`Handle`]s
     #[cfg_attr(feature = "igri", inspect(skip))]
     tx: Sender<Message>,
+    /// Generation of newly pushed entries. Raised when trailing entries are removed, so that weak
+    /// handles to them never resolve to a new item.
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    new_entry_gen: Gen,
+    /// Latest generations of slots rolled back by [`Pool::restore`]. The slots are refilled with
+    /// newer generations, so that weak handles to discarded items never resolve to a new item.
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    gen_floors: HashMap<Slot, Gen>,
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    config: PoolConfig,
+    /// Handles to items moved out with [`Pool::move_into`], keyed by the slot and generation they
+    /// refer to
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    redirects: HashMap<(Slot, Gen), Redirect>,
+    /// Called just before an item is invalidated
+    #[derivative(Debug = "ignore")]
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    on_invalidate: Option<InvalidateHook<T>>,
+    #[derivative(Debug = "ignore")]
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    observer: Option<Box<dyn PoolObserver<T>>>,
+    #[cfg(not(feature = "allocator_api"))]
+    #[derivative(Debug = "ignore")]
+    #[cfg_attr(feature = "igri", inspect(skip))]
+    _alloc: PhantomData<fn() -> A>,
 }
 
 #[cfg(feature = "igri")]
-fn inspect_pool<'a, T>(pool: &'a mut Pool<T>, ui: &igri::imgui::Ui, label: &str)
+fn inspect_pool<'a, T, A: Allocator>(pool: &'a mut Pool<T, A>, ui: &igri::imgui::Ui, label: &str)
 where
     T: igri::Inspect,
 {
@@ -192,125 +559,1010 @@ where
 
 impl<T> Pool<T> {
     pub fn with_capacity(cap: usize) -> Self {
+        Self::from_entry_vec(Vec::with_capacity(cap))
+    }
+
+    /// Creates an empty pool reporting to the observer
+    pub fn with_observer(observer: impl PoolObserver<T> + 'static) -> Self {
+        let mut pool = Self::with_capacity(0);
+        pool.observer = Some(Box::new(observer));
+        pool
+    }
+
+    pub fn builder() -> PoolBuilder<T> {
+        PoolBuilder::new()
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T, A: Allocator> Pool<T, A> {
+    /// Creates an empty pool storing its entries in the allocator
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_capacity_in(0, alloc)
+    }
+
+    /// Creates an empty pool storing its entries in the allocator
+    pub fn with_capacity_in(cap: usize, alloc: A) -> Self {
+        Self::from_entry_vec(Vec::with_capacity_in(cap, alloc))
+    }
+}
+
+impl<T, A: Allocator> Pool<T, A> {
+    /// Creates an empty pool around the empty entry storage
+    fn from_entry_vec(
+        #[cfg(feature = "allocator_api")] entries: Vec<PoolEntry<T>, A>,
+        #[cfg(not(feature = "allocator_api"))] entries: Vec<PoolEntry<T>>,
+    ) -> Self {
         let (tx, rx) = smpsc::unbounded();
         Self {
-            entries: Vec::with_capacity(cap),
+            entries,
+            n_live: 0,
             rx,
             tx,
+            new_entry_gen: unsafe { Gen::new_unchecked(1) },
+            gen_floors: HashMap::new(),
+            config: PoolConfig::default(),
+            redirects: HashMap::new(),
+            on_invalidate: None,
+            observer: None,
+            #[cfg(not(feature = "allocator_api"))]
+            _alloc: PhantomData,
+        }
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+}
+
+/// Fluent constructor of a [`Pool`]
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct PoolBuilder<T> {
+    capacity: usize,
+    generation_start: Gen,
+    config: PoolConfig,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for PoolBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PoolBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            generation_start: unsafe { Gen::new_unchecked(1) },
+            config: PoolConfig::default(),
+            _ty: PhantomData,
+        }
+    }
+
+    pub fn capacity(mut self, cap: usize) -> Self {
+        self.capacity = cap;
+        self
+    }
+
+    /// Generation of newly pushed entries. Zero is treated as one
+    pub fn generation_start(mut self, gen: u32) -> Self {
+        self.generation_start = Gen::new(gen).unwrap_or(self.generation_start);
+        self
+    }
+
+    pub fn on_zero_policy(mut self, policy: OnZeroPolicy) -> Self {
+        self.config.on_zero_policy = policy;
+        self
+    }
+
+    pub fn auto_shrink_fraction(mut self, fraction: f32) -> Self {
+        self.config.auto_shrink_fraction = Some(fraction);
+        self
+    }
+
+    pub fn generation_overflow(mut self, policy: GenerationOverflowPolicy) -> Self {
+        self.config.generation_overflow = policy;
+        self
+    }
+
+    pub fn build(self) -> Pool<T> {
+        let mut pool = Pool::with_capacity(self.capacity);
+        pool.new_entry_gen = self.generation_start;
+        pool.config = self.config;
+        pool
+    }
+}
+
+/// The clone has a fresh channel, so no existing [`Handle`] reports to it. Reference counts are
+/// copied as-is. The invalidation hook and the observer are not cloned.
+impl<T: Clone> Clone for Pool<T> {
+    fn clone(&self) -> Self {
+        let mut pool = Self::with_capacity(0);
+        pool.clone_from(self);
+        pool
+    }
+
+    /// Reuses the allocation of `self`. The invalidation hook and the observer of `self` are kept
+    fn clone_from(&mut self, source: &Self) {
+        self.entries.clone_from(&source.entries);
+        self.n_live = source.n_live;
+        self.new_entry_gen = source.new_entry_gen;
+        self.gen_floors.clone_from(&source.gen_floors);
+        self.config = source.config;
+
+        let (tx, rx) = smpsc::unbounded();
+        self.tx = tx;
+        self.rx = rx;
+        self.redirects.clear();
+    }
+}
+
+/// Warns about items still referred to by strong handles in debug builds
+///
+/// NOTE: Because of this `Drop` impl, data borrowed by items must strictly outlive the pool (drop
+/// check). For example, a `Pool<&'a T>` can no longer be declared before the `T` it borrows in the
+/// same scope.
+impl<T, A: Allocator> Drop for Pool<T, A> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            if let Some(warning) = self.outstanding_handles_warning() {
+                eprintln!("{}", warning);
+            }
         }
     }
 }
 
 /// # ----- Reference counter synchronization --
-impl<T> Pool<T> {
+impl<T, A: Allocator> Pool<T, A> {
     /// Update reference counts letting user visit item with zero reference counts.
-    pub fn sync_refcounts(&mut self, mut on_zero: impl FnMut(&mut Self, Slot)) {
-        while let Some(mes) = self.rx.recv() {
+    ///
+    /// The callback may add or remove items. Messages queued while syncing are processed on the
+    /// next sync.
+    pub fn sync_refcounts(&mut self, on_zero: impl FnMut(&mut Self, Slot)) {
+        let res = self.try_sync_refcounts(on_zero);
+        debug_assert!(res.is_ok(), "reference count underflow");
+    }
+
+    /// Same as [`Pool::sync_refcounts`], but returns an error instead of asserting on a drop message
+    /// for an item with zero reference count. Such messages are skipped (the count saturates at
+    /// zero) and the rest of the queue is still processed.
+    ///
+    /// Messages from handles of an older generation are always ignored.
+    pub fn try_sync_refcounts(
+        &mut self,
+        mut on_zero: impl FnMut(&mut Self, Slot),
+    ) -> Result<(), PoolError> {
+        self.sync_refcounts_impl(&mut (), |pool, _, slot| on_zero(pool, slot))
+    }
+
+    /// Same as [`Pool::sync_refcounts`], but threads a user context through the callback so that it
+    /// can update external state without capturing it
+    pub fn sync_refcounts_with<C>(
+        &mut self,
+        ctx: &mut C,
+        on_zero: impl FnMut(&mut Self, &mut C, Slot),
+    ) {
+        let res = self.sync_refcounts_impl(ctx, on_zero);
+        debug_assert!(res.is_ok(), "reference count underflow");
+    }
+
+    fn sync_refcounts_impl<C>(
+        &mut self,
+        ctx: &mut C,
+        mut on_zero: impl FnMut(&mut Self, &mut C, Slot),
+    ) -> Result<(), PoolError> {
+        let mut res = Ok(());
+
+        // drain first so that callbacks can add or remove items (and drop handles) without
+        // affecting this pass. Messages sent by callbacks are processed on the next sync
+        let msgs = std::iter::from_fn(|| self.rx.recv()).collect::<Vec<_>>();
+
+        for mes in msgs {
+            if self.forward(&mes) {
+                continue;
+            }
             match mes {
-                Message::New(slot) => {
-                    let e = &mut self.entries[slot.to_usize()];
+                Message::New(slot, gen) => {
+                    // the entry may have been removed with `shrink_to`, or retired with the
+                    // generation of the handle
+                    let e = match self.entries.get_mut(slot.to_usize()) {
+                        Some(e) if e.gen == gen && e.data.is_some() => e,
+                        _ => continue,
+                    };
                     e.ref_count += 1;
                 }
-                Message::Drop(slot) => {
-                    let entry = &mut self.entries[slot.to_usize()];
+                Message::Drop(slot, gen) => {
+                    let entry = match self.entries.get_mut(slot.to_usize()) {
+                        Some(e) if e.gen == gen && e.data.is_some() => e,
+                        _ => continue,
+                    };
+                    if entry.ref_count == 0 {
+                        res = Err(PoolError::RefCountUnderflow);
+                        continue;
+                    }
                     entry.ref_count -= 1;
                     if entry.ref_count == 0 {
-                        on_zero(self, slot);
+                        on_zero(self, ctx, slot);
                     }
                 }
             }
         }
+
+        res
     }
 
-    /// Updates reference counts and invalidates unreferenced items
-    pub fn sync_refcounts_and_invalidate(&mut self) {
-        self.sync_refcounts(|p, slot| {
-            p.invalidate_unreferenced(slot);
-        })
+    /// Updates reference counts and returns the slots that reached zero reference count, without
+    /// invalidating them
+    ///
+    /// Pull-based counterpart of [`Pool::sync_refcounts`]; call
+    /// [`Pool::invalidate_unreferenced`] on the slots to remove them. Only slots whose count is
+    /// still zero after all the messages are applied are returned (e.g. not an item whose last
+    /// handle was dropped before another one was made with [`Pool::upgrade`]), each once.
+    pub fn take_unreferenced(&mut self) -> Vec<Slot> {
+        let mut slots = Vec::new();
+        self.sync_refcounts_with(&mut slots, |_, slots, slot| slots.push(slot));
+
+        let mut seen = HashSet::with_capacity(slots.len());
+        slots.retain(|slot| {
+            let entry = &self.entries[slot.to_usize()];
+            entry.data.is_some() && entry.ref_count == 0 && seen.insert(*slot)
+        });
+        slots
     }
 
-    /// Invalidates an entry with zero reference count manually
-    pub fn invalidate_unreferenced(&mut self, slot: Slot) -> bool {
-        let e = &mut self.entries[slot.to_usize()];
-        assert!(e.ref_count == 0);
-        if e.data.is_none() {
-            return false;
+    /// Takes out live items with zero reference count as of the last sync, without syncing
+    ///
+    /// Reclaim step for [`OnZeroPolicy::KeepUntilManual`]. Note that slot-addressed items (see
+    /// [`Pool::get_or_insert_at`]) always have zero reference count and are drained too.
+    pub fn drain_zero_ref(&mut self) -> Vec<(Slot, T)> {
+        let mut items = Vec::new();
+        for i in 0..self.entries.len() {
+            if self.entries[i].ref_count != 0 {
+                continue;
+            }
+            if let Some(item) = self.take_data(i) {
+                items.push((Slot(i as u32), item));
+            }
         }
-        e.data = None;
-        true
+        items
     }
-}
 
-/// # ----- Handle-based accessors -----
-impl<T> Pool<T> {
-    /// TODO: Consider tracking empty slot
-    fn find_empty_slot(&mut self) -> Option<usize> {
-        for i in 0..self.entries.len() {
-            if let Some(entry) = self.entries.get(i) {
-                if entry.data.is_none() {
-                    return Some(i);
+    /// Updates reference counts and invalidates unreferenced items, moving them into a batch instead
+    /// of dropping them
+    ///
+    /// Drop the batch when it's convenient to run heavy destructors. The invalidation hook and the
+    /// observer are called as usual. Unlike [`Pool::sync_refcounts_and_invalidate`], the
+    /// [`OnZeroPolicy`] is not considered. Items upgraded again before the sync are kept.
+    pub fn collect_garbage(&mut self) -> GarbageBatch<T> {
+        let items = self
+            .take_unreferenced()
+            .into_iter()
+            .filter_map(|slot| {
+                if self.entries[slot.to_usize()].ref_count != 0 {
+                    return None;
                 }
+                let item = self.invalidate_data(slot.to_usize())?;
+                Some((slot, item))
+            })
+            .collect();
+        GarbageBatch { items }
+    }
+
+    /// Drains the message queue and counts live items still referred to by strong handles
+    fn count_outstanding_handles(&mut self) -> usize {
+        let _ = self.sync_refcounts_impl(&mut (), |_, _, _| {});
+        self.items_with_handles()
+    }
+
+    /// Warning printed on drop if there are outstanding strong handles
+    fn outstanding_handles_warning(&mut self) -> Option<String> {
+        let n = self.count_outstanding_handles();
+        if n == 0 {
+            return None;
+        }
+        Some(format!(
+            "toy_pool: pool dropped while {} item(s) are referred to by strong handles",
+            n
+        ))
+    }
+
+    /// Applies reference count deltas from an external source, invalidating items whose count
+    /// reaches zero (unless the [`OnZeroPolicy`] is [`OnZeroPolicy::KeepUntilManual`])
+    ///
+    /// This bypasses the internal message queue for users who track handles by themselves. Counts
+    /// are clamped to the range of [`RefCount`], and out-of-bounds or vacant slots are ignored.
+    pub fn apply_messages(&mut self, msgs: impl IntoIterator<Item = (Slot, i32)>) {
+        for (slot, delta) in msgs {
+            let entry = match self.entries.get_mut(slot.to_usize()) {
+                Some(e) if e.data.is_some() => e,
+                _ => continue,
+            };
+
+            let prev = entry.ref_count;
+            let count = (prev as i64 + delta as i64).clamp(0, RefCount::MAX as i64);
+            entry.ref_count = count as RefCount;
+
+            if prev > 0 && count == 0 && self.config.on_zero_policy == OnZeroPolicy::Invalidate {
+                self.invalidate_unreferenced(slot);
             }
         }
-        None
     }
 
-    /// Inserts the item and returns a strong [`Handle`] for it
-    pub fn add(&mut self, item: impl Into<T>) -> Handle<T> {
-        let item = item.into();
+    /// Updates reference counts and invalidates unreferenced items
+    ///
+    /// With [`OnZeroPolicy::KeepUntilManual`], unreferenced items are kept. See also
+    /// [`PoolConfig::auto_shrink_fraction`].
+    pub fn sync_refcounts_and_invalidate(&mut self) {
+        match self.config.on_zero_policy {
+            OnZeroPolicy::Invalidate => self.sync_refcounts(|p, slot| {
+                p.invalidate_unreferenced(slot);
+            }),
+            OnZeroPolicy::KeepUntilManual => self.sync_refcounts(|_, _| {}),
+        }
+        self.auto_shrink();
+    }
 
-        let (gen, slot) = match self.find_empty_slot() {
-            Some(i) => {
-                let entry = &mut self.entries[i];
-                entry.data = Some(item);
-                entry.gen = Gen::new(entry.gen.get() + 1).expect("Generation overflow!");
-                (entry.gen, i)
+    /// Same as [`Pool::sync_refcounts_and_invalidate`], but nets the queued messages per item before
+    /// applying them
+    ///
+    /// An item is invalidated only if its count ends at zero, so a handle dropped just before
+    /// another one is created (e.g. with [`Pool::upgrade`]) doesn't invalidate the item.
+    pub fn sync_coalesced(&mut self) {
+        let mut order = Vec::new();
+        let mut deltas = HashMap::<(Slot, Gen), i64>::new();
+        while let Some(mes) = self.rx.recv() {
+            if self.forward(&mes) {
+                continue;
             }
-            None => {
-                let gen = unsafe { Gen::new_unchecked(1) };
-                let entry = PoolEntry {
-                    data: Some(item),
-                    gen,
-                    // count the initial handle below
-                    ref_count: 1,
-                };
+            let (key, delta) = match mes {
+                Message::New(slot, gen) => ((slot, gen), 1),
+                Message::Drop(slot, gen) => ((slot, gen), -1),
+            };
+            *deltas.entry(key).or_insert_with(|| {
+                order.push(key);
+                0
+            }) += delta;
+        }
 
-                let i = self.entries.len();
-                self.entries.push(entry);
-                (gen, i)
+        let mut underflow = false;
+        for (slot, gen) in order {
+            let delta = deltas[&(slot, gen)];
+            let entry = match self.entries.get_mut(slot.to_usize()) {
+                Some(e) if e.gen == gen && e.data.is_some() => e,
+                _ => continue,
+            };
+            if delta == 0 {
+                continue;
             }
-        };
 
-        Handle {
-            slot: Slot(slot as u32),
-            gen,
-            sender: self.tx.clone(),
-            _ty: Default::default(),
+            let count = entry.ref_count as i64 + delta;
+            if count < 0 {
+                underflow = true;
+                // like `sync_refcounts`, drops for an unreferenced item are skipped
+                if entry.ref_count == 0 {
+                    continue;
+                }
+            }
+            entry.ref_count = count.clamp(0, RefCount::MAX as i64) as RefCount;
+
+            if entry.ref_count == 0 && self.config.on_zero_policy == OnZeroPolicy::Invalidate {
+                self.invalidate_unreferenced(slot);
+            }
         }
+        debug_assert!(!underflow, "reference count underflow");
+
+        self.auto_shrink();
     }
 
-    /// Tries to get a reference from a [`WeakHandle`]
-    pub fn get(&self, weak: &WeakHandle<T>) -> Option<&T> {
-        let entry = &self.entries[weak.slot.to_usize()];
-        if entry.gen == weak.gen {
-            entry.data.as_ref()
-        } else {
-            None
+    /// Shrinks the pool if it's more fragmented than [`PoolConfig::auto_shrink_fraction`]
+    fn auto_shrink(&mut self) {
+        if let Some(fraction) = self.config.auto_shrink_fraction {
+            if self.fragmentation() > fraction {
+                self.shrink_to_fit();
+            }
+        }
+    }
+
+    /// Syncs with [`Pool::sync_refcounts_and_invalidate`] and then checks if the weak handle still
+    /// refers to a live item
+    ///
+    /// Unlike [`Pool::get`], this reflects drops of strong handles queued since the last sync.
+    pub fn is_alive_eventual(&mut self, weak: &WeakHandle<T>) -> bool {
+        self.sync_refcounts_and_invalidate();
+        self.get(weak).is_some()
+    }
+
+    /// Invalidates an entry with zero reference count manually
+    pub fn invalidate_unreferenced(&mut self, slot: Slot) -> bool {
+        match self.try_invalidate(slot) {
+            Ok(()) => true,
+            Err(PoolError::VacantSlot) => false,
+            Err(err) => panic!("failed to invalidate slot {}: {}", slot.0, err),
+        }
+    }
+
+    /// Invalidates an entry with zero reference count manually, failing if it's out of bounds,
+    /// still referenced or already vacant
+    pub fn try_invalidate(&mut self, slot: Slot) -> Result<(), PoolError> {
+        let e = self
+            .entries
+            .get_mut(slot.to_usize())
+            .ok_or(PoolError::SlotOutOfBounds)?;
+        if e.ref_count != 0 {
+            return Err(PoolError::Referenced);
+        }
+        if e.data.is_none() {
+            return Err(PoolError::VacantSlot);
+        }
+        self.drop_data(slot.to_usize());
+        Ok(())
+    }
+
+    /// Invalidates an entry regardless of its reference count. Returns `false` if it's vacant
+    ///
+    /// The slot gets a new generation, so the remaining strong handles to the item are no longer
+    /// valid (indexing with them panics) and their messages are ignored.
+    pub fn force_invalidate(&mut self, slot: Slot) -> bool {
+        let i = slot.to_usize();
+        match self.entries.get(i) {
+            Some(e) if e.data.is_some() => {}
+            _ => return false,
+        }
+
+        self.drop_data(i);
+        let e = &mut self.entries[i];
+        e.vacate_gen();
+        e.ref_count = 0;
+        true
+    }
+
+    /// Sets a callback invoked with each item just before it's removed from the pool
+    ///
+    /// That is, by [`Pool::invalidate_unreferenced`] (including the one on sync),
+    /// [`Pool::try_invalidate`], [`Pool::force_invalidate`], [`Pool::collect_garbage`],
+    /// [`Pool::retain_mut`], [`Pool::drain_while`], [`Pool::drain_zero_ref`],
+    /// [`iter::Cursor::remove_current`] and [`Pool::move_into`]. Items discarded by
+    /// [`Pool::restore`] or dropped with the pool are not reported.
+    pub fn set_invalidate_hook(&mut self, f: impl FnMut(Slot, &T) + 'static) {
+        self.on_invalidate = Some(Box::new(f));
+    }
+
+    /// Drops the item, calling the invalidation hook and the observer first
+    fn drop_data(&mut self, i: usize) {
+        drop(self.invalidate_data(i));
+    }
+
+    /// Takes the item out, calling the invalidation hook and the observer first
+    fn invalidate_data(&mut self, i: usize) -> Option<T> {
+        let entry = &mut self.entries[i];
+        if let (Some(data), Some(hook)) = (&entry.data, &mut self.on_invalidate) {
+            hook(Slot(i as u32), data);
+        }
+        if let (Some(data), Some(observer)) = (&entry.data, &mut self.observer) {
+            observer.on_invalidate(Slot(i as u32), data);
+        }
+        let data = entry.data.take();
+        if data.is_some() {
+            self.n_live -= 1;
+        }
+        data
+    }
+}
+
+/// # ----- Diagnostics -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Number of live items, O(1)
+    pub fn live_count(&self) -> usize {
+        self.n_live
+    }
+
+    /// Number of vacant entries, O(1)
+    pub fn vacant_count(&self) -> usize {
+        self.entries.len() - self.n_live
+    }
+
+    /// Number of reference counting messages queued since the last sync
+    ///
+    /// A growing backlog means the pool should be synced more often.
+    pub fn pending_messages(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Sum of reference counts of live items as of the last synchronization
+    ///
+    /// If it stays high when you expect no handles, some [`Handle`]s are leaking.
+    pub fn total_refcount(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|e| e.data.is_some())
+            .map(|e| e.ref_count as u64)
+            .sum()
+    }
+
+    /// Number of live items referred to by strong handles as of the last synchronization
+    ///
+    /// Compare with [`Pool::total_refcount`] (number of strong handles) and the number of live
+    /// items to find orphaned items waiting for invalidation.
+    pub fn items_with_handles(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.data.is_some() && e.ref_count > 0)
+            .count()
+    }
+
+    /// Ratio of vacant entries to all entries; `0.0` is dense and near `1.0` is mostly holes
+    ///
+    /// Use it to decide when to compact or shrink the pool.
+    pub fn fragmentation(&self) -> f32 {
+        if self.entries.is_empty() {
+            return 0.0;
+        }
+        self.vacant_count() as f32 / self.entries.len() as f32
+    }
+
+    /// Highest generation across all entries (one for an empty pool)
+    ///
+    /// Monitor it to act before generations overflow (see also [`Pool::generation_headroom`]).
+    pub fn max_generation(&self) -> u32 {
+        self.entries.iter().map(|e| e.gen.get()).max().unwrap_or(1)
+    }
+
+    /// Number of reuses left before the busiest slot exhausts its generations
+    ///
+    /// Consider [`Pool::reset_all_vacant_generations`] or [`Pool::compact`] when it gets low.
+    pub fn generation_headroom(&self) -> u32 {
+        u32::MAX - self.max_generation()
+    }
+
+    /// Explains why the weak handle does (or doesn't) resolve, for debugging dangling references
+    pub fn explain_miss(&self, weak: &WeakHandle<T>) -> String {
+        let (slot, gen) = (weak.slot, weak.gen.get());
+        let entry = match self.entries.get(slot.to_usize()) {
+            Some(e) => e,
+            None => return format!("slot {} out of bounds (len {})", slot, self.entries.len()),
+        };
+
+        let current = entry.gen.get();
+        match (current == gen, entry.data.is_some()) {
+            (true, true) => format!("slot {} gen {} is live", slot, gen),
+            (true, false) => format!("slot {} gen {} is vacant (item invalidated)", slot, gen),
+            (false, live) => format!(
+                "slot {} gen mismatch: handle gen {}, current gen {} ({})",
+                slot,
+                gen,
+                current,
+                if live {
+                    "item recycled"
+                } else {
+                    "item removed"
+                }
+            ),
+        }
+    }
+
+    /// Returns a bitset where bit `i` is set iff slot `i` has an item
+    ///
+    /// Useful for comparing the structure of pools without touching the items.
+    pub fn liveness_bits(&self) -> Vec<u64> {
+        let mut bits = vec![0u64; self.entries.len().div_ceil(64)];
+        for (i, e) in self.entries.iter().enumerate() {
+            if e.data.is_some() {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        bits
+    }
+
+    /// Returns metadata of every entry, including vacant ones
+    pub fn debug_entries(&self) -> impl Iterator<Item = DebugEntry> + '_ {
+        self.entries.iter().enumerate().map(|(i, e)| DebugEntry {
+            slot: Slot(i as u32),
+            gen: e.gen.get(),
+            present: e.data.is_some(),
+            ref_count: e.ref_count,
+        })
+    }
+}
+
+/// # ----- Comparison -----
+impl<T: PartialEq, A: Allocator> Pool<T, A> {
+    /// Checks if both pools have the same live items at the same slots with the same generations
+    ///
+    /// Vacant entries, reference counts and capacity are not compared.
+    pub fn content_eq(&self, other: &Pool<T, A>) -> bool {
+        self.iter_gen().eq(other.iter_gen())
+    }
+
+    /// Checks if both pools have the same live items in slot order, ignoring slots and generations
+    pub fn values_eq(&self, other: &Pool<T, A>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+/// # ----- Handle-based accessors -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// TODO: Consider tracking empty slot
+    fn find_empty_slot(&self) -> Option<usize> {
+        let retire = self.config.generation_overflow == GenerationOverflowPolicy::Retire;
+        for i in 0..self.entries.len() {
+            if let Some(entry) = self.entries.get(i) {
+                if entry.data.is_none() && !(retire && entry.is_retired()) {
+                    return Some(i);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the slot that the next [`Pool::add`] would use (the first vacant slot, or a new one)
+    pub fn peek_next_slot(&self) -> Slot {
+        Slot(self.find_empty_slot().unwrap_or(self.entries.len()) as u32)
+    }
+
+    /// Inserts the item and returns a strong [`Handle`] for it
+    pub fn add(&mut self, item: impl Into<T>) -> Handle<T> {
+        self.try_add(item).expect("Generation overflow!")
+    }
+
+    /// Inserts the item and returns a strong [`Handle`] for it, failing if the generation of the
+    /// reused slot overflows
+    pub fn try_add(&mut self, item: impl Into<T>) -> Result<Handle<T>, PoolError> {
+        // count the initial handle below
+        let (gen, slot) = self.insert(item.into(), 1)?;
+
+        Ok(Handle {
+            slot: Slot(slot as u32),
+            gen,
+            sender: self.tx.clone(),
+            _ty: Default::default(),
+        })
+    }
+
+    /// Inserts the item without reallocating, returning it back if there's no vacant slot or spare
+    /// capacity (or the generation of the vacant slot overflows)
+    pub fn try_add_no_grow(&mut self, item: T) -> Result<Handle<T>, T> {
+        let fits = match self.find_empty_slot() {
+            Some(i) => self.entries[i].next_gen().is_some(),
+            None => self.entries.len() < self.entries.capacity(),
+        };
+        if !fits {
+            return Err(item);
+        }
+
+        Ok(self.try_add(item).unwrap())
+    }
+
+    /// Inserts the item and returns a strong [`Handle`] for it along with its slot
+    pub fn add_returning_slot(&mut self, item: impl Into<T>) -> (Handle<T>, Slot) {
+        let handle = self.add(item);
+        let slot = handle.slot;
+        (handle, slot)
+    }
+
+    /// Inserts the item and returns a strong [`Handle`] for it along with a mutable reference to it
+    pub fn add_and_get_mut(&mut self, item: impl Into<T>) -> (Handle<T>, &mut T) {
+        let handle = self.add(item);
+        let item = self.entries[handle.slot.to_usize()].data.as_mut().unwrap();
+        (handle, item)
+    }
+
+    /// Puts the item into the first empty slot or a new entry
+    fn insert(&mut self, item: T, ref_count: RefCount) -> Result<(Gen, usize), PoolError> {
+        match self.find_empty_slot() {
+            Some(i) => {
+                if self.entries[i].next_gen().is_none() {
+                    return Err(PoolError::GenerationOverflow);
+                }
+                Ok((self.fill_vacant(i, item, ref_count), i))
+            }
+            None => {
+                let i = self.entries.len();
+                Ok((self.push_entry(item, ref_count), i))
+            }
+        }
+    }
+
+    /// Pushes a new entry with the item
+    fn push_entry(&mut self, item: T, ref_count: RefCount) -> Gen {
+        let gen = self.new_entry_gen;
+        let entry = PoolEntry {
+            data: Some(item),
+            gen,
+            ref_count,
+        };
+
+        assert!(
+            self.entries.len() < Slot::INVALID.to_usize(),
+            "Slot overflow!"
+        );
+        self.entries.push(entry);
+        self.n_live += 1;
+        gen
+    }
+
+    /// Puts the item into the vacant entry, or pushes a new entry for it (after vacant ones as
+    /// needed). Only filling an existing entry is reported to the observer as a reuse
+    fn put_at(&mut self, i: usize, item: T, ref_count: RefCount) -> Gen {
+        if i < self.entries.len() {
+            self.fill_vacant(i, item, ref_count)
+        } else {
+            self.grow_to(i);
+            self.push_entry(item, ref_count)
+        }
+    }
+
+    /// Puts the item into a vacant entry with a new generation
+    fn fill_vacant(&mut self, i: usize, item: T, ref_count: RefCount) -> Gen {
+        self.bump_entry_gen(i);
+        let entry = &mut self.entries[i];
+        debug_assert!(entry.data.is_none());
+        entry.data = Some(item);
+        entry.ref_count = ref_count;
+        self.n_live += 1;
+        let gen = entry.gen;
+        if let Some(observer) = &mut self.observer {
+            observer.on_reuse(Slot(i as u32), gen.get());
+        }
+        gen
+    }
+
+    /// Advances the generation of the entry for a new item, past the generations discarded by
+    /// [`Pool::restore`]
+    fn bump_entry_gen(&mut self, i: usize) {
+        let entry = &mut self.entries[i];
+        if let Some(floor) = self.gen_floors.remove(&Slot(i as u32)) {
+            entry.gen = cmp::max(entry.gen, floor);
+        }
+        entry.bump_gen();
+    }
+
+    /// Takes the item out of the entry, advancing the generation so that handles to it (and their
+    /// pending messages) are rejected. The invalidation hook and the observer are called first
+    fn take_data(&mut self, i: usize) -> Option<T> {
+        self.entries.get(i)?.data.as_ref()?;
+        let data = self.invalidate_data(i);
+        let entry = &mut self.entries[i];
+        entry.vacate_gen();
+        entry.ref_count = 0;
+        data
+    }
+
+    /// Pushes vacant entries until there are at least `len` of them
+    fn grow_to(&mut self, len: usize) {
+        if self.entries.len() >= len {
+            return;
+        }
+        let gen = self.new_entry_gen;
+        self.entries.resize_with(len, || PoolEntry {
+            data: None,
+            gen,
+            ref_count: 0,
+        });
+    }
+
+    /// Inserts the item into a known vacant slot and returns a strong [`Handle`] for it
+    ///
+    /// The slot gets a new generation, so weak handles to the previous item stay invalid. Panics if
+    /// the slot is out of bounds or occupied, or if its generation is exhausted. See
+    /// [`Pool::try_reinsert_at`].
+    pub fn reinsert_at(&mut self, slot: Slot, item: T) -> Handle<T> {
+        match self.try_reinsert_at(slot, item) {
+            Ok(handle) => handle,
+            Err(PoolError::OccupiedSlot) => panic!("tried to reinsert into an occupied slot"),
+            Err(err) => panic!("failed to reinsert at slot {}: {}", slot, err),
+        }
+    }
+
+    /// Same as [`Pool::reinsert_at`], but fails if the slot is out of bounds
+    /// ([`PoolError::SlotOutOfBounds`]), occupied ([`PoolError::OccupiedSlot`]) or retired
+    /// ([`PoolError::GenerationOverflow`])
+    pub fn try_reinsert_at(&mut self, slot: Slot, item: T) -> Result<Handle<T>, PoolError> {
+        let i = slot.to_usize();
+        let entry = self.entries.get(i).ok_or(PoolError::SlotOutOfBounds)?;
+        if entry.data.is_some() {
+            return Err(PoolError::OccupiedSlot);
+        }
+        if entry.next_gen().is_none() {
+            return Err(PoolError::GenerationOverflow);
+        }
+        let gen = self.fill_vacant(i, item, 1);
+
+        Ok(Handle {
+            slot,
+            gen,
+            sender: self.tx.clone(),
+            _ty: Default::default(),
+        })
+    }
+
+    /// Tries to get a reference from a [`WeakHandle`]
+    pub fn get(&self, weak: &WeakHandle<T>) -> Option<&T> {
+        // the slot may have been removed with `shrink_to`
+        let entry = self.entries.get(weak.slot.to_usize())?;
+        if entry.gen == weak.gen {
+            entry.data.as_ref()
+        } else {
+            None
         }
     }
 
     /// Tries to get a mutable reference from a [`WeakHandle`]
     pub fn get_mut(&mut self, weak: &WeakHandle<T>) -> Option<&mut T> {
-        let entry = &mut self.entries[weak.slot.to_usize()];
+        let entry = self.entries.get_mut(weak.slot.to_usize())?;
         if entry.gen == weak.gen {
             entry.data.as_mut()
         } else {
             None
         }
     }
+
+    /// Resolves the weak handles, skipping stale ones
+    pub fn get_all<'a>(&'a self, weaks: &'a [WeakHandle<T>]) -> impl Iterator<Item = &'a T> + 'a {
+        weaks.iter().filter_map(move |weak| self.get(weak))
+    }
+
+    /// Resolves the weak handles, keeping positions (`None` for stale ones)
+    pub fn get_many<const N: usize>(&self, weaks: [&WeakHandle<T>; N]) -> [Option<&T>; N] {
+        weaks.map(|weak| self.get(weak))
+    }
+}
+
+/// # ----- Fallible accessors -----
+impl<T, A: Allocator> Pool<T, A> {
+    fn try_entry(&self, slot: Slot, gen: Gen) -> Result<&PoolEntry<T>, PoolError> {
+        let entry = self
+            .entries
+            .get(slot.to_usize())
+            .ok_or(PoolError::SlotOutOfBounds)?;
+        if entry.gen != gen {
+            return Err(PoolError::StaleGeneration);
+        }
+        Ok(entry)
+    }
+
+    /// Checks if indexing with the strong handle succeeds. It fails only after
+    /// [`Pool::force_invalidate`] or a removal (e.g. [`Pool::retain_mut`])
+    pub fn handle_is_valid(&self, handle: &Handle<T>) -> bool {
+        self.try_index(handle).is_ok()
+    }
+
+    /// Fallible version of the `Index` implementation
+    pub fn try_index(&self, handle: &Handle<T>) -> Result<&T, PoolError> {
+        let entry = self.try_entry(handle.slot, handle.gen)?;
+        entry.data.as_ref().ok_or(PoolError::VacantSlot)
+    }
+
+    /// Fallible version of the `IndexMut` implementation
+    pub fn try_index_mut(&mut self, handle: &Handle<T>) -> Result<&mut T, PoolError> {
+        self.try_entry(handle.slot, handle.gen)?;
+        let entry = &mut self.entries[handle.slot.to_usize()];
+        entry.data.as_mut().ok_or(PoolError::VacantSlot)
+    }
+
+    /// Returns mutable references to two entries at distinct indices
+    fn entries2_mut(&mut self, a: usize, b: usize) -> (&mut PoolEntry<T>, &mut PoolEntry<T>) {
+        assert!(a != b);
+        if a < b {
+            let (left, right) = self.entries.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = self.entries.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+
+    /// Returns mutable references to the items at two distinct slots
+    pub fn try_get2_mut_by_slot(
+        &mut self,
+        a: Slot,
+        b: Slot,
+    ) -> Result<(&mut T, &mut T), PoolError> {
+        let (a, b) = (a.to_usize(), b.to_usize());
+        if a == b {
+            return Err(PoolError::DuplicateSlot);
+        }
+        if cmp::max(a, b) >= self.entries.len() {
+            return Err(PoolError::SlotOutOfBounds);
+        }
+
+        match self.entries2_mut(a, b) {
+            (PoolEntry { data: Some(a), .. }, PoolEntry { data: Some(b), .. }) => Ok((a, b)),
+            _ => Err(PoolError::VacantSlot),
+        }
+    }
+}
+
+/// Result of [`Pool::pair_mut`]
+#[derive(Debug, PartialEq)]
+pub enum PairMut<'a, T> {
+    /// The handles refer to distinct items
+    Two(&'a mut T, &'a mut T),
+    /// The handles refer to the same item
+    Same(&'a mut T),
+    /// At least one of the handles is no longer valid
+    Dead,
+}
+
+/// Result of [`Pool::pair_mut_by_weak`]
+#[derive(Debug, PartialEq)]
+pub enum PairResult<'a, T> {
+    /// The handles refer to distinct items
+    Distinct(&'a mut T, &'a mut T),
+    /// The handles refer to the same item
+    Aliased(&'a mut T),
+    /// Only the first handle is no longer valid
+    FirstDead,
+    /// Only the second handle is no longer valid
+    SecondDead,
+    BothDead,
+}
+
+/// # ----- Pair accessors -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Returns mutable references to the items of two strong handles, which may be the same
+    pub fn pair_mut(&mut self, a: &Handle<T>, b: &Handle<T>) -> PairMut<'_, T> {
+        if self.try_index(a).is_err() || self.try_index(b).is_err() {
+            return PairMut::Dead;
+        }
+
+        let (a, b) = (a.slot.to_usize(), b.slot.to_usize());
+        if a == b {
+            return PairMut::Same(self.entries[a].data.as_mut().unwrap());
+        }
+
+        match self.entries2_mut(a, b) {
+            (PoolEntry { data: Some(a), .. }, PoolEntry { data: Some(b), .. }) => {
+                PairMut::Two(a, b)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns mutable references to the items of two weak handles, telling which is invalid or if
+    /// they alias
+    pub fn pair_mut_by_weak(&mut self, a: &WeakHandle<T>, b: &WeakHandle<T>) -> PairResult<'_, T> {
+        match (self.get(a).is_some(), self.get(b).is_some()) {
+            (true, true) => {}
+            (false, true) => return PairResult::FirstDead,
+            (true, false) => return PairResult::SecondDead,
+            (false, false) => return PairResult::BothDead,
+        }
+
+        let (a, b) = (a.slot.to_usize(), b.slot.to_usize());
+        if a == b {
+            return PairResult::Aliased(self.entries[a].data.as_mut().unwrap());
+        }
+
+        match self.entries2_mut(a, b) {
+            (PoolEntry { data: Some(a), .. }, PoolEntry { data: Some(b), .. }) => {
+                PairResult::Distinct(a, b)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns mutable references to the items of N strong handles. Returns `None` if any of them
+    /// is stale or two of them share a slot
+    pub fn get_disjoint_handles_mut<const N: usize>(
+        &mut self,
+        handles: [&Handle<T>; N],
+    ) -> Option<[&mut T; N]> {
+        for (i, h) in handles.iter().enumerate() {
+            self.try_index(h).ok()?;
+            if handles[..i].iter().any(|other| other.slot == h.slot) {
+                return None;
+            }
+        }
+
+        let entries = self.entries.as_mut_ptr();
+        // SAFETY: the slots are in bounds, live and distinct
+        Some(handles.map(|h| unsafe { (*entries.add(h.slot.to_usize())).data.as_mut().unwrap() }))
+    }
 }
 
-impl<T> ops::Index<&Handle<T>> for Pool<T> {
+impl<T, A: Allocator> ops::Index<&Handle<T>> for Pool<T, A> {
     type Output = T;
     fn index(&self, handle: &Handle<T>) -> &Self::Output {
         let entry = &self.entries[handle.slot.to_usize()];
@@ -322,7 +1574,7 @@ impl<T> ops::Index<&Handle<T>> for Pool<T> {
     }
 }
 
-impl<T> ops::IndexMut<&Handle<T>> for Pool<T> {
+impl<T, A: Allocator> ops::IndexMut<&Handle<T>> for Pool<T, A> {
     fn index_mut(&mut self, handle: &Handle<T>) -> &mut Self::Output {
         let entry = &mut self.entries[handle.slot.to_usize()];
         debug_assert!(entry.ref_count > 0);
@@ -334,22 +1586,19 @@ impl<T> ops::IndexMut<&Handle<T>> for Pool<T> {
 }
 
 /// # ----- Slot-based accessors -----
-impl<T> Pool<T> {
+impl<T, A: Allocator> Pool<T, A> {
     /// Tries to upgrade the weak handle to a strong handle. Fails if it's already removed or IF THE
     /// REF COUNT IS ALREADY ZERO. This is for protecting [`Pool::sync_refcounts`], but this design
     /// may change.
     pub fn upgrade(&self, weak: &WeakHandle<T>) -> Option<Handle<T>> {
-        let slot = weak.slot.to_usize();
-        if slot > self.entries.len() {
-            return None;
-        }
-
-        let entry = &self.entries[slot];
+        let entry = self.entries.get(weak.slot.to_usize())?;
         if entry.ref_count == 0 {
             return None;
         }
 
         if entry.gen == weak.gen {
+            // count the new handle
+            self.tx.send(Message::New(weak.slot, weak.gen));
             Some(Handle {
                 slot: weak.slot,
                 gen: weak.gen,
@@ -361,57 +1610,1017 @@ impl<T> Pool<T> {
         }
     }
 
-    /// Retruns the item if it's valid
-    pub fn get_by_slot(&self, slot: Slot) -> Option<&T> {
+    /// Creates a weak handle to the live item at the slot
+    pub fn weak_for_slot(&self, slot: Slot) -> Option<WeakHandle<T>> {
+        let entry = self.entries.get(slot.to_usize())?;
+        entry.data.as_ref()?;
+        Some(WeakHandle {
+            slot,
+            gen: entry.gen,
+            _ty: PhantomData,
+        })
+    }
+
+    /// Upgrades each weak handle, preserving order
+    pub fn upgrade_all(&self, weaks: &[WeakHandle<T>]) -> Vec<Option<Handle<T>>> {
+        weaks.iter().map(|weak| self.upgrade(weak)).collect()
+    }
+
+    /// Returns the item if the slot is live with the generation. See [`Pool::snapshot_slots`]
+    pub fn get_by_slot_gen(&self, slot: Slot, gen: u32) -> Option<&T> {
         let entry = self.entries.get(slot.to_usize())?;
+        if entry.gen.get() == gen {
+            entry.data.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Captures `(slot, generation)` of live items, to be resolved later with
+    /// [`Pool::get_by_slot_gen`]
+    ///
+    /// Items removed (or replaced in their slots) after the snapshot fail to resolve.
+    pub fn snapshot_slots(&self) -> Vec<(Slot, u32)> {
+        self.iter_gen().map(|(slot, gen, _)| (slot, gen)).collect()
+    }
+
+    /// Retruns the item if it's valid
+    pub fn get_by_slot(&self, slot: impl AsSlot) -> Option<&T> {
+        let entry = self.entries.get(slot.as_slot().to_usize())?;
         entry.data.as_ref()
     }
 
     /// Retruns the item if it's valid
-    pub fn get_mut_by_slot(&mut self, slot: Slot) -> Option<&mut T> {
-        let entry = self.entries.get_mut(slot.to_usize())?;
+    pub fn get_mut_by_slot(&mut self, slot: impl AsSlot) -> Option<&mut T> {
+        let entry = self.entries.get_mut(slot.as_slot().to_usize())?;
         entry.data.as_mut()
     }
 
-    /// Returns slots of existing items. NOTE: It contains unreferenced items as long as they're not
-    /// yet removed.
-    pub fn slots(&self) -> impl Iterator<Item = Slot> + '_ {
-        self.entries.iter().enumerate().filter_map(|(i, entry)| {
-            if entry.data.is_some() {
-                Some(Slot(i as u32))
-            } else {
-                None
-            }
-        })
+    /// Returns a raw pointer to the live item at the slot
+    ///
+    /// The pointer is valid only until the pool is mutated (adding items may reallocate the
+    /// backing store, and syncing may drop the item). It must not be written through.
+    pub fn data_ptr_by_slot(&self, slot: Slot) -> Option<*const T> {
+        self.get_by_slot(slot).map(|data| data as *const T)
     }
-}
 
-/// # ----- Iterators -----
-impl<T> Pool<T> {
-    /// Returns an iterator of valid items in this pool
-    pub fn iter(&self) -> impl Iterator<Item = &T>
-    where
-        T: 'static,
-    {
-        iter::Iter {
-            entries: self.entries.iter(),
+    /// Returns a raw mutable pointer to the live item at the slot
+    ///
+    /// Same contract as [`Pool::data_ptr_by_slot`]. In addition, no other reference to the item may
+    /// be alive while it's written through.
+    pub fn data_ptr_mut_by_slot(&mut self, slot: Slot) -> Option<*mut T> {
+        self.get_mut_by_slot(slot).map(|data| data as *mut T)
+    }
+
+    /// Replaces the item at the live slot, returning the old one. Returns `None` if it's vacant
+    ///
+    /// The generation is kept, so existing handles refer to the new item.
+    pub fn replace_by_slot(&mut self, slot: Slot, item: T) -> Option<T> {
+        self.get_mut_by_slot(slot)
+            .map(|data| mem::replace(data, item))
+    }
+
+    /// Returns the item at the slot, inserting one made with `f` if the slot is vacant
+    ///
+    /// Entries are grown as needed. Items inserted this way are not referred to by any [`Handle`]
+    /// (zero reference count), so they're not invalidated on sync. Panics if the slot is retired or
+    /// is [`Slot::INVALID`]. See [`Pool::try_get_or_insert_at`].
+    pub fn get_or_insert_at(&mut self, slot: Slot, f: impl FnOnce() -> T) -> &mut T {
+        match self.try_get_or_insert_at(slot, f) {
+            Ok(data) => data,
+            Err(PoolError::SlotOutOfBounds) => panic!("tried to insert at the invalid slot"),
+            Err(_) => panic!("Generation overflow!"),
         }
     }
 
-    /// Returns an mutable iterator of valid items in this pool
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T>
-    where
-        T: 'static,
-    {
-        iter::IterMut {
-            entries: self.entries.iter_mut(),
+    /// Same as [`Pool::get_or_insert_at`], but fails instead of panicking if the slot is vacant with
+    /// an exhausted generation ([`PoolError::GenerationOverflow`]) or is [`Slot::INVALID`]
+    /// ([`PoolError::SlotOutOfBounds`])
+    pub fn try_get_or_insert_at(
+        &mut self,
+        slot: Slot,
+        f: impl FnOnce() -> T,
+    ) -> Result<&mut T, PoolError> {
+        if slot == Slot::INVALID {
+            return Err(PoolError::SlotOutOfBounds);
+        }
+        let i = slot.to_usize();
+        if self.get_by_slot(slot).is_none() {
+            if matches!(self.entries.get(i), Some(e) if e.is_retired()) {
+                return Err(PoolError::GenerationOverflow);
+            }
+            self.put_at(i, f(), 0);
         }
+        Ok(self.entries[i].data.as_mut().unwrap())
     }
 
-    /// Returns an iterator of `(Slot, &T)`
-    pub fn enumerate_items(&self) -> impl Iterator<Item = (Slot, &T)> {
-        self.entries.iter().enumerate().filter_map(|(i, entry)| {
-            if let Some(data) = &entry.data {
+    /// Places items at their slots with the given generations, e.g. to restore a saved layout
+    ///
+    /// Entries are grown as needed and the items are slot-addressed (zero reference count). Fails
+    /// without inserting anything if a slot is given twice ([`PoolError::DuplicateSlot`]), already
+    /// has an item ([`PoolError::OccupiedSlot`]) or is [`Slot::INVALID`]
+    /// ([`PoolError::SlotOutOfBounds`]), or if a new generation is requested for a retired slot
+    /// ([`PoolError::GenerationOverflow`]).
+    ///
+    /// A generation of zero requests a new one, as [`Pool::get_or_insert_at`] gives: a vacant slot
+    /// advances its generation, and a new entry gets the starting generation of the pool (see
+    /// [`PoolBuilder::generation_start`]). Other generations are set as-is, so use them on fresh
+    /// slots: a weak handle to a former item of a slot may resolve to the new item if the given
+    /// generation matches.
+    pub fn insert_many_at(
+        &mut self,
+        items: impl IntoIterator<Item = (Slot, u32, T)>,
+    ) -> Result<(), PoolError> {
+        let items = items.into_iter().collect::<Vec<_>>();
+
+        let mut seen = HashSet::with_capacity(items.len());
+        for (slot, gen, _) in &items {
+            if *slot == Slot::INVALID {
+                return Err(PoolError::SlotOutOfBounds);
+            }
+            if !seen.insert(*slot) {
+                return Err(PoolError::DuplicateSlot);
+            }
+            if self.get_by_slot(*slot).is_some() {
+                return Err(PoolError::OccupiedSlot);
+            }
+            if *gen == 0 && matches!(self.entries.get(slot.to_usize()), Some(e) if e.is_retired()) {
+                return Err(PoolError::GenerationOverflow);
+            }
+        }
+
+        for (slot, gen, item) in items {
+            let i = slot.to_usize();
+            let gen = match Gen::new(gen) {
+                Some(gen) => gen,
+                None => {
+                    self.put_at(i, item, 0);
+                    continue;
+                }
+            };
+
+            self.grow_to(i + 1);
+            let entry = &mut self.entries[i];
+            entry.data = Some(item);
+            entry.gen = gen;
+            entry.ref_count = 0;
+            self.n_live += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the item at the slot, inserting a default value if the slot is vacant
+    pub fn get_or_insert_default(&mut self, slot: Slot) -> &mut T
+    where
+        T: Default,
+    {
+        self.get_or_insert_at(slot, T::default)
+    }
+
+    /// Returns the live item with the lowest slot
+    pub fn first(&self) -> Option<(Slot, &T)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .find_map(|(i, e)| e.data.as_ref().map(|data| (Slot(i as u32), data)))
+    }
+
+    /// Returns the live item with the lowest slot
+    pub fn first_mut(&mut self) -> Option<(Slot, &mut T)> {
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, e)| e.data.as_mut().map(|data| (Slot(i as u32), data)))
+    }
+
+    /// Returns the live item with the highest slot
+    pub fn last(&self) -> Option<(Slot, &T)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, e)| e.data.as_ref().map(|data| (Slot(i as u32), data)))
+    }
+
+    /// Returns the live item with the highest slot
+    pub fn last_mut(&mut self) -> Option<(Slot, &mut T)> {
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .find_map(|(i, e)| e.data.as_mut().map(|data| (Slot(i as u32), data)))
+    }
+
+    /// Returns the items as a contiguous slice if the pool is dense
+    ///
+    /// NOTE: Items are stored as `Option<T>` inside entries, so they are never laid out contiguously.
+    /// Currently this returns `Some` only for a pool without entries and `None` otherwise.
+    pub fn try_as_slice(&self) -> Option<&[T]> {
+        if self.entries.is_empty() {
+            Some(&[])
+        } else {
+            None
+        }
+    }
+
+    /// Returns the items as a contiguous slice along with their slots if the pool is dense
+    ///
+    /// Same limitation as [`Pool::try_as_slice`]: `Some` only for a pool without entries. Use
+    /// [`Pool::dense_indices`] to pack items into a buffer by yourself.
+    pub fn try_dense_view(&self) -> Option<(&[T], Vec<Slot>)> {
+        self.try_as_slice().map(|items| (items, Vec::new()))
+    }
+
+    /// Maps live slots to dense indices `0..n` in slot order, returning the map and its inverse
+    ///
+    /// The mapping is stable until items are added or removed, e.g. for packing items into a
+    /// contiguous GPU buffer each frame.
+    pub fn dense_indices(&self) -> (HashMap<Slot, u32>, Vec<Slot>) {
+        let slots = self.slots().collect::<Vec<_>>();
+        let map = slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| (*slot, i as u32))
+            .collect();
+        (map, slots)
+    }
+
+    /// Returns slots of existing items. NOTE: It contains unreferenced items as long as they're not
+    /// yet removed.
+    pub fn slots(&self) -> impl Iterator<Item = Slot> + '_ {
+        self.entries.iter().enumerate().filter_map(|(i, entry)| {
+            if entry.data.is_some() {
+                Some(Slot(i as u32))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// # ----- Entry API -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Returns a view into the slot for read-or-insert operations
+    pub fn entry(&mut self, slot: Slot) -> Entry<'_, T, A> {
+        Entry { pool: self, slot }
+    }
+}
+
+/// View into a slot of a [`Pool`], which may be vacant. Created with [`Pool::entry`]
+///
+/// Items inserted through it are slot-addressed (see [`Pool::get_or_insert_at`]).
+#[derive(Debug)]
+pub struct Entry<'a, T, A: Allocator = Global> {
+    pool: &'a mut Pool<T, A>,
+    slot: Slot,
+}
+
+impl<'a, T, A: Allocator> Entry<'a, T, A> {
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Inserts the item if the slot is vacant and returns the item in the slot
+    pub fn or_insert(self, item: T) -> &'a mut T {
+        self.pool.get_or_insert_at(self.slot, || item)
+    }
+
+    /// Inserts an item made with `f` if the slot is vacant and returns the item in the slot
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        self.pool.get_or_insert_at(self.slot, f)
+    }
+
+    /// Modifies the item if the slot is occupied
+    pub fn and_modify(self, f: impl FnOnce(&mut T)) -> Self {
+        if let Some(item) = self.pool.get_mut_by_slot(self.slot) {
+            f(item);
+        }
+        self
+    }
+}
+
+/// # ----- Split borrows -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Returns a mutable reference to the item at the slot and a read-only view of the other items
+    ///
+    /// This lets you read other items while mutating one, which is not possible while iterating
+    /// with [`Pool::iter_mut`].
+    pub fn split_at_slot_mut(&mut self, slot: Slot) -> Option<(&mut T, PoolView<'_, T>)> {
+        let hole = slot.to_usize();
+        if hole >= self.entries.len() {
+            return None;
+        }
+
+        let (left, rest) = self.entries.split_at_mut(hole);
+        let (center, right) = rest.split_first_mut().unwrap();
+        let item = center.data.as_mut()?;
+        Some((item, PoolView { left, right }))
+    }
+
+    /// Splits live items into those with slots below `at` and the rest, as two disjoint mutable
+    /// iterators of `(Slot, &mut T)`
+    pub fn split_live_mut(
+        &mut self,
+        at: Slot,
+    ) -> (
+        impl Iterator<Item = (Slot, &mut T)>,
+        impl Iterator<Item = (Slot, &mut T)>,
+    ) {
+        let at = cmp::min(at.to_usize(), self.entries.len());
+        let (left, right) = self.entries.split_at_mut(at);
+        (live_items_mut(left, 0), live_items_mut(right, at))
+    }
+}
+
+/// Live items of the entries with their slots, where the first entry is at slot `offset`
+fn live_items_mut<T>(
+    entries: &mut [PoolEntry<T>],
+    offset: usize,
+) -> impl Iterator<Item = (Slot, &mut T)> {
+    entries.iter_mut().enumerate().filter_map(move |(i, e)| {
+        let data = e.data.as_mut()?;
+        Some((Slot((offset + i) as u32), data))
+    })
+}
+
+/// Read-only view of a [`Pool`] without one mutably borrowed slot. Created with
+/// [`Pool::split_at_slot_mut`]
+#[derive(Debug)]
+pub struct PoolView<'a, T> {
+    /// Entries before the hole
+    left: &'a [PoolEntry<T>],
+    /// Entries after the hole
+    right: &'a [PoolEntry<T>],
+}
+
+impl<'a, T> PoolView<'a, T> {
+    /// Slot that is excluded from the view
+    pub fn hole(&self) -> Slot {
+        Slot(self.left.len() as u32)
+    }
+
+    fn entry(&self, slot: Slot) -> Option<&'a PoolEntry<T>> {
+        let i = slot.to_usize();
+        let hole = self.left.len();
+        match i.cmp(&hole) {
+            cmp::Ordering::Less => self.left.get(i),
+            cmp::Ordering::Equal => None,
+            cmp::Ordering::Greater => self.right.get(i - hole - 1),
+        }
+    }
+
+    /// Tries to get a reference from a [`WeakHandle`]. Returns `None` for the hole
+    pub fn get(&self, weak: &WeakHandle<T>) -> Option<&'a T> {
+        let entry = self.entry(weak.slot)?;
+        if entry.gen == weak.gen {
+            entry.data.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Retruns the item if it's valid. Returns `None` for the hole
+    pub fn get_by_slot(&self, slot: impl AsSlot) -> Option<&'a T> {
+        self.entry(slot.as_slot())?.data.as_ref()
+    }
+
+    /// Returns an iterator of valid items except the one in the hole
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> {
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .filter_map(|e| e.data.as_ref())
+    }
+}
+
+/// # ----- Removal -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Visits every live item with mutable access, removing the ones for which `f` returns `false`
+    ///
+    /// Removed slots get a new generation, so handles to removed items are rejected. Indexing the
+    /// pool with a strong handle to a removed item panics.
+    pub fn retain_mut(&mut self, mut f: impl FnMut(Slot, &mut T) -> bool) {
+        for i in 0..self.entries.len() {
+            let keep = match &mut self.entries[i].data {
+                Some(data) => f(Slot(i as u32), data),
+                None => continue,
+            };
+            if !keep {
+                self.take_data(i);
+            }
+        }
+    }
+
+    /// Same as [`Pool::retain_mut`], but threads a user context through the predicate so that it
+    /// can update external state without capturing it
+    pub fn retain_with<C>(&mut self, ctx: &mut C, mut pred: impl FnMut(&mut C, Slot, &T) -> bool) {
+        self.retain_mut(|slot, item| pred(ctx, slot, item));
+    }
+
+    /// Same as [`Pool::retain_mut`], but stops early when `f` returns `Break`
+    ///
+    /// `Continue(true)` keeps the item and `Continue(false)` removes it. The item for which `f`
+    /// returns `Break` is kept.
+    pub fn drain_while(&mut self, mut f: impl FnMut(Slot, &mut T) -> ops::ControlFlow<(), bool>) {
+        for i in 0..self.entries.len() {
+            let flow = match &mut self.entries[i].data {
+                Some(data) => f(Slot(i as u32), data),
+                None => continue,
+            };
+            match flow {
+                ops::ControlFlow::Continue(true) => {}
+                ops::ControlFlow::Continue(false) => {
+                    self.take_data(i);
+                }
+                ops::ControlFlow::Break(()) => return,
+            }
+        }
+    }
+}
+
+/// # ----- Capacity -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Number of entries the pool can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    /// Reserves capacity for at least `additional` more entries (see [`Vec::reserve`])
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    /// Reserves capacity for exactly `additional` more entries without amortized growth (see
+    /// [`Vec::reserve_exact`])
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.entries.reserve_exact(additional);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more entries (see [`Vec::try_reserve`])
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.entries.try_reserve(additional)
+    }
+
+    /// Removes trailing vacant entries, keeping at least `min_capacity` of them, and shrinks the
+    /// capacity of the backing store with the lower bound (see [`Vec::shrink_to`])
+    ///
+    /// Live items never move. Removed slots are handed out again with a newer generation, so weak
+    /// handles to them stay invalid.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        while self.entries.len() > min_capacity {
+            let last = self.entries.last().unwrap();
+            if last.data.is_some() {
+                break;
+            }
+            // generations discarded by `restore` count as handed out
+            let slot = Slot(self.entries.len() as u32 - 1);
+            let gen = match self.gen_floors.get(&slot) {
+                Some(&floor) => cmp::max(last.gen, floor),
+                None => last.gen,
+            };
+            let next_gen = match gen.get().checked_add(1).and_then(Gen::new) {
+                Some(gen) => gen,
+                // keep the exhausted slot rather than reusing its generations
+                None => break,
+            };
+
+            self.new_entry_gen = cmp::max(self.new_entry_gen, next_gen);
+            self.gen_floors.remove(&slot);
+            self.entries.pop();
+        }
+
+        self.entries.shrink_to(min_capacity);
+    }
+
+    /// Removes trailing vacant entries and shrinks the capacity of the backing store as much as
+    /// possible
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+}
+
+/// # ----- Generations -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Resets the generation of every vacant slot to one, returning the number of slots changed
+    ///
+    /// This is a maintenance operation for long-running pools to stave off generation overflow.
+    /// WARNING: weak handles to the previous items in those slots look valid again once the slots
+    /// are reused with a matching generation. Only call it when no such weak handles are kept.
+    pub fn reset_all_vacant_generations(&mut self) -> usize {
+        let first = unsafe { Gen::new_unchecked(1) };
+        let mut n = 0;
+        for entry in self.entries.iter_mut() {
+            if entry.data.is_none() && entry.gen != first {
+                entry.gen = first;
+                n += 1;
+            }
+        }
+        n
+    }
+}
+
+/// # ----- Reordering -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Moves the item at `from` into the vacant slot `to`
+    ///
+    /// Both slots get a new generation, so handles to the item are invalidated and their messages
+    /// are ignored. So the moved item is left with zero reference count (slot-addressed).
+    fn move_entry(&mut self, from: usize, to: usize) {
+        let (src, dst) = self.entries2_mut(from, to);
+        debug_assert!(src.data.is_some() && dst.data.is_none());
+        dst.data = src.data.take();
+        dst.ref_count = 0;
+        src.ref_count = 0;
+        src.vacate_gen();
+        self.bump_entry_gen(to);
+    }
+
+    /// Moves live items to the front keeping their order, returning a map from old slots to new
+    /// slots of the moved items
+    ///
+    /// Moved items get new generations, so existing handles to them are invalidated (rejected
+    /// rather than resolving to another item). They're left with zero reference count
+    /// (slot-addressed), so they're not invalidated on sync. Items not in the map keep their slots
+    /// and handles.
+    pub fn compact(&mut self) -> HashMap<Slot, Slot> {
+        let mut remap = HashMap::new();
+        let mut to = 0;
+        for from in 0..self.entries.len() {
+            if self.entries[from].data.is_none() {
+                continue;
+            }
+            while self.entries[to].is_retired() {
+                to += 1;
+            }
+            if from != to {
+                self.move_entry(from, to);
+                remap.insert(Slot(from as u32), Slot(to as u32));
+            }
+            to += 1;
+        }
+        remap
+    }
+
+    /// Moves the item at the slot into the lowest vacant slot if it's lower, returning the new slot
+    ///
+    /// Returns `None` if the slot is vacant or there's no lower vacant slot. Like
+    /// [`Pool::compact`], the moved item gets a new generation, so existing handles to it are
+    /// invalidated and it's left with zero reference count (slot-addressed).
+    pub fn promote(&mut self, slot: Slot) -> Option<Slot> {
+        let from = slot.to_usize();
+        self.entries.get(from)?.data.as_ref()?;
+        let to = self.entries[..from]
+            .iter()
+            .position(|e| e.data.is_none() && !e.is_retired())?;
+        self.move_entry(from, to);
+        Some(Slot(to as u32))
+    }
+
+    /// Incremental version of [`Pool::compact`]: moves up to `max_moves` live items toward the
+    /// front, adding them to the remap. Returns `true` when the pool is fully compacted
+    ///
+    /// Running steps to completion gives the same result as one [`Pool::compact`] call, provided
+    /// that no item is added or removed in between.
+    pub fn compact_step(&mut self, max_moves: usize, remap: &mut HashMap<Slot, Slot>) -> bool {
+        let mut to = 0;
+        let mut moves = 0;
+        for from in 0..self.entries.len() {
+            if self.entries[from].data.is_none() {
+                continue;
+            }
+            while self.entries[to].is_retired() {
+                to += 1;
+            }
+            if from != to {
+                if moves == max_moves {
+                    return false;
+                }
+                self.move_entry(from, to);
+                remap.insert(Slot(from as u32), Slot(to as u32));
+                moves += 1;
+            }
+            to += 1;
+        }
+        true
+    }
+
+    /// Syncs reference counts, invalidates unreferenced items and compacts the pool
+    ///
+    /// See [`Pool::compact`]: strong handles to moved items are invalidated, and the returned map
+    /// can be used to fix up slots of weak handles or external indices.
+    pub fn sweep(&mut self) -> HashMap<Slot, Slot> {
+        self.sync_refcounts_and_invalidate();
+        let remap = self.compact();
+        self.auto_shrink();
+        remap
+    }
+
+    /// Sorts live items and packs them to the front, returning a map from old slots to new slots
+    ///
+    /// Every slot whose occupant changes gets a new generation, so existing handles to moved items
+    /// are invalidated (rejected rather than resolving to another item). Moved items are left with
+    /// zero reference count (slot-addressed) as no valid handle refers to them. Use the returned map
+    /// to fix up external indices.
+    ///
+    /// Slots with exhausted generations (see [`GenerationOverflowPolicy::Retire`]) can't take another
+    /// item, so they're skipped and their items are moved elsewhere. New entries are pushed if
+    /// needed.
+    pub fn sort_by(&mut self, mut cmp: impl FnMut(&T, &T) -> cmp::Ordering) -> HashMap<Slot, Slot> {
+        let mut live = Vec::new();
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            if let Some(data) = entry.data.take() {
+                live.push((i, data, entry.ref_count));
+                entry.ref_count = 0;
+            }
+        }
+
+        // stable sort: equal items keep their relative order
+        live.sort_by(|a, b| cmp(&a.1, &b.1));
+
+        let mut remap = HashMap::with_capacity(live.len());
+        let mut was_live = vec![false; self.entries.len()];
+        for (old, _, _) in &live {
+            was_live[*old] = true;
+        }
+
+        let mut targets = (0..self.entries.len())
+            .filter(|&i| self.entries[i].next_gen().is_some())
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        for (old, data, ref_count) in live {
+            let new = targets.next().unwrap_or_else(|| {
+                let i = self.entries.len();
+                self.grow_to(i + 1);
+                i
+            });
+            if old == new {
+                self.entries[new].ref_count = ref_count;
+            } else {
+                // messages from the old handles are rejected from now on
+                self.bump_entry_gen(new);
+            }
+            self.entries[new].data = Some(data);
+            remap.insert(Slot(old as u32), Slot(new as u32));
+        }
+
+        // vacated slots
+        for (entry, was_live) in self.entries.iter_mut().zip(was_live) {
+            if was_live && entry.data.is_none() {
+                entry.vacate_gen();
+            }
+        }
+
+        remap
+    }
+}
+
+/// # ----- Snapshots -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Consumes the pool and returns its entries, including vacant ones
+    pub fn into_parts(mut self) -> Vec<PoolEntrySnapshot<T>> {
+        self.entries
+            .drain(..)
+            .map(|e| PoolEntrySnapshot {
+                data: e.data,
+                gen: e.gen.get(),
+                ref_count: e.ref_count,
+            })
+            .collect()
+    }
+
+    /// Consumes the pool and returns live items in slot order
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.entries.drain(..).filter_map(|e| e.data).collect()
+    }
+}
+
+impl<T> Pool<T> {
+    /// Creates a pool from entries, keeping their slots and generations
+    ///
+    /// The pool has a fresh channel, so no existing [`Handle`] reports to it. Reference counts are
+    /// restored as-is.
+    pub fn from_parts(parts: Vec<PoolEntrySnapshot<T>>) -> Self {
+        let mut pool = Self::with_capacity(0);
+        pool.entries = parts
+            .into_iter()
+            .map(|e| PoolEntry {
+                data: e.data,
+                gen: Gen::new(e.gen).unwrap_or(pool.new_entry_gen),
+                ref_count: e.ref_count,
+            })
+            .collect();
+        pool.n_live = pool.entries.iter().filter(|e| e.data.is_some()).count();
+        pool
+    }
+}
+
+/// # ----- Transactions -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Saves the items and generations of this pool so that it can be rolled back with
+    /// [`Pool::restore`]
+    pub fn snapshot(&self) -> PoolSnapshot<T>
+    where
+        T: Clone,
+    {
+        PoolSnapshot {
+            entries: self.entries.to_vec(),
+            new_entry_gen: self.new_entry_gen,
+        }
+    }
+
+    /// Replaces the entries with the snapshot
+    ///
+    /// Slots and generations are restored, so weak handles created before the snapshot resolve
+    /// again, while handles created after the snapshot become invalid. Slots refilled later get
+    /// generations newer than the discarded ones, so those handles stay invalid.
+    ///
+    /// The pool gets a fresh channel: no existing [`Handle`] reports to it any more, and reference
+    /// counts are restored as-is. The invalidation hook is not called for discarded items.
+    pub fn restore(&mut self, snap: PoolSnapshot<T>) {
+        // entries pushed after the snapshot are discarded; don't let their weak handles resolve to
+        // items pushed later
+        // (and keep the generation raised by `shrink_to` after the snapshot)
+        let mut new_entry_gen = cmp::max(self.new_entry_gen, snap.new_entry_gen);
+        for e in self.entries.iter().skip(snap.entries.len()) {
+            if let Some(gen) = e.next_gen() {
+                new_entry_gen = cmp::max(new_entry_gen, gen);
+            }
+        }
+
+        // same for generations handed out to the other slots after the snapshot
+        for (i, (e, old)) in self.entries.iter().zip(&snap.entries).enumerate() {
+            if e.gen > old.gen {
+                let floor = self.gen_floors.entry(Slot(i as u32)).or_insert(e.gen);
+                *floor = cmp::max(*floor, e.gen);
+            }
+        }
+
+        self.entries.clear();
+        self.entries.extend(snap.entries);
+        self.n_live = self.entries.iter().filter(|e| e.data.is_some()).count();
+        self.new_entry_gen = new_entry_gen;
+
+        let (tx, rx) = smpsc::unbounded();
+        self.tx = tx;
+        self.rx = rx;
+        self.redirects.clear();
+    }
+}
+
+/// # ----- Moving items between pools -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Moves the items at the slots into another pool, returning their new slots and strong handles
+    ///
+    /// Vacant slots are skipped. The source slots get a new generation, so existing handles to the
+    /// moved items are rejected by this pool.
+    ///
+    /// Existing strong handles still count in the other pool: their pending messages are forwarded
+    /// right away (see [`Pool::redirect_pending`]), and later ones are forwarded when this pool is
+    /// synced. So keep syncing this pool while such handles are alive.
+    pub fn move_into(&mut self, other: &mut Pool<T, A>, slots: &[Slot]) -> Vec<(Slot, Handle<T>)> {
+        let mut moved_slots = Vec::new();
+        let moved = slots
+            .iter()
+            .filter_map(|&slot| {
+                let entry = self.entries.get(slot.to_usize())?;
+                let (gen, count) = (entry.gen, entry.ref_count);
+                let item = self.take_data(slot.to_usize())?;
+
+                let handle = other.add(item);
+                other.entries[handle.slot.to_usize()].ref_count += count;
+                let redirect = Redirect {
+                    tx: other.tx.clone(),
+                    slot: handle.slot,
+                    gen: handle.gen,
+                    count,
+                };
+                self.redirects.insert((slot, gen), redirect);
+                moved_slots.push(slot);
+
+                Some((handle.slot(), handle))
+            })
+            .collect::<Vec<_>>();
+
+        for mes in self.take_pending(&moved_slots) {
+            if !self.forward(&mes) {
+                // from handles to older items
+                self.tx.send(mes);
+            }
+        }
+        // now the counts are exact
+        self.redirects.retain(|_, r| r.count > 0);
+
+        moved
+    }
+
+    /// Sends pending messages for the slots to the other pool without syncing this pool
+    ///
+    /// Messages from handles to items moved with [`Pool::move_into`] are retagged with the new
+    /// slots and generations. Others are sent as-is, e.g. for items reinserted into the other pool
+    /// at the same slots with the same generations ([`Pool::insert_many_at`]).
+    pub fn redirect_pending(&mut self, slots: &[Slot], dest: &mut Pool<T, A>) {
+        for mes in self.take_pending(slots) {
+            if !self.forward(&mes) {
+                dest.tx.send(mes);
+            }
+        }
+    }
+
+    /// Removes pending messages for the slots from the queue, keeping the order of the rest
+    fn take_pending(&mut self, slots: &[Slot]) -> Vec<Message> {
+        let msgs = std::iter::from_fn(|| self.rx.recv()).collect::<Vec<_>>();
+        let mut taken = Vec::new();
+        for mes in msgs {
+            let slot = match mes {
+                Message::New(slot, _) | Message::Drop(slot, _) => slot,
+            };
+            if slots.contains(&slot) {
+                taken.push(mes);
+            } else {
+                self.tx.send(mes);
+            }
+        }
+        taken
+    }
+
+    /// Forwards the message if it's from a handle to a moved item
+    fn forward(&mut self, mes: &Message) -> bool {
+        let (slot, gen, delta) = match *mes {
+            Message::New(slot, gen) => (slot, gen, 1),
+            Message::Drop(slot, gen) => (slot, gen, -1),
+        };
+        let redirect = match self.redirects.get_mut(&(slot, gen)) {
+            Some(r) => r,
+            None => return false,
+        };
+
+        redirect.tx.send(match mes {
+            Message::New(..) => Message::New(redirect.slot, redirect.gen),
+            Message::Drop(..) => Message::Drop(redirect.slot, redirect.gen),
+        });
+
+        if delta > 0 {
+            redirect.count += 1;
+        } else {
+            redirect.count = redirect.count.saturating_sub(1);
+            if redirect.count == 0 {
+                // no handle is left to report
+                self.redirects.remove(&(slot, gen));
+            }
+        }
+        true
+    }
+}
+
+/// Swaps the items at two slots in two different pools. Returns false if either slot is vacant
+///
+/// Generations and reference counts stay with their slots; only the items move. So handles to
+/// `sa` in `a` keep resolving after the swap, but now to the item that came from `b` (and vice
+/// versa). A handle does NOT follow its item to the other pool.
+pub fn swap_data<T, A: Allocator>(
+    a: &mut Pool<T, A>,
+    sa: Slot,
+    b: &mut Pool<T, A>,
+    sb: Slot,
+) -> bool {
+    let ea = match a.entries.get_mut(sa.to_usize()) {
+        Some(e) if e.data.is_some() => e,
+        _ => return false,
+    };
+    let eb = match b.entries.get_mut(sb.to_usize()) {
+        Some(e) if e.data.is_some() => e,
+        _ => return false,
+    };
+    mem::swap(&mut ea.data, &mut eb.data);
+    true
+}
+
+/// # ----- Merging -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Moves all live items of the other pool into this pool, returning a map from their slots in
+    /// the other pool to their new slots
+    ///
+    /// Handles of the other pool don't refer to the moved items. They are inserted with zero
+    /// reference count (slot-addressed), so they're not invalidated on sync.
+    pub fn append(&mut self, mut other: Pool<T, A>) -> HashMap<Slot, Slot> {
+        let mut remap = HashMap::new();
+        for (i, entry) in other.entries.drain(..).enumerate() {
+            if let Some(item) = entry.data {
+                let (_gen, new) = self.insert(item, 0).expect("Generation overflow!");
+                remap.insert(Slot(i as u32), Slot(new as u32));
+            }
+        }
+        remap
+    }
+}
+
+/// # ----- Iterators -----
+impl<T, A: Allocator> Pool<T, A> {
+    /// Returns an iterator of valid items in this pool
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        iter::Iter {
+            entries: self.entries.iter(),
+        }
+    }
+
+    /// Returns an mutable iterator of valid items in this pool
+    ///
+    /// The pool is borrowed while iterating. To read other items while mutating one, use
+    /// [`Pool::split_at_slot_mut`].
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        iter::IterMut {
+            entries: self.entries.iter_mut(),
+        }
+    }
+
+    /// Returns an iterator of valid items from the highest slot down. It doesn't allocate
+    pub fn iter_rev(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().rev().filter_map(|e| e.data.as_ref())
+    }
+
+    /// Returns a cursor at the first live item, which can remove and insert items while traversing
+    pub fn cursor_mut(&mut self) -> iter::Cursor<'_, T, A> {
+        iter::Cursor::new(self)
+    }
+
+    /// Returns an mutable iterator of valid items from the highest slot down
+    pub fn iter_mut_rev(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries
+            .iter_mut()
+            .rev()
+            .filter_map(|e| e.data.as_mut())
+    }
+
+    /// Returns a mutable iterator of valid items matching the predicate
+    pub fn iter_mut_where(&mut self, pred: impl Fn(&T) -> bool) -> impl Iterator<Item = &mut T> {
+        self.iter_mut().filter(move |item| pred(item))
+    }
+
+    /// Applies `f` to every live item in place
+    pub fn map_each_mut(&mut self, mut f: impl FnMut(Slot, &mut T)) {
+        for (slot, item) in self.enumerate_items_mut() {
+            f(slot, item);
+        }
+    }
+
+    /// Counts live items matching the predicate
+    pub fn count_where(&self, pred: impl Fn(&T) -> bool) -> usize {
+        self.entries
+            .iter()
+            .filter_map(|e| e.data.as_ref())
+            .filter(|data| pred(data))
+            .count()
+    }
+
+    /// Checks if any live item matches the predicate, stopping at the first match
+    pub fn any(&self, pred: impl Fn(&T) -> bool) -> bool {
+        self.iter().any(pred)
+    }
+
+    /// Checks if every live item matches the predicate, stopping at the first mismatch
+    ///
+    /// Returns `true` for an empty pool.
+    pub fn all(&self, pred: impl Fn(&T) -> bool) -> bool {
+        self.iter().all(pred)
+    }
+
+    /// Visits every live item with mutable access, along with the live items within `radius` slots
+    /// of it
+    pub fn for_each_with_neighbors_mut(
+        &mut self,
+        radius: usize,
+        mut f: impl FnMut(Slot, &mut T, &[(Slot, &T)]),
+    ) {
+        for i in 0..self.entries.len() {
+            let (left, rest) = self.entries.split_at_mut(i);
+            let (center, right) = rest.split_first_mut().unwrap();
+            let center = match &mut center.data {
+                Some(data) => data,
+                None => continue,
+            };
+
+            let lo = i.saturating_sub(radius);
+            let left = left[lo..].iter().enumerate().map(|(j, e)| (lo + j, e));
+            let right = right
+                .iter()
+                .take(radius)
+                .enumerate()
+                .map(|(j, e)| (i + 1 + j, e));
+            let neighbors = left
+                .chain(right)
+                .filter_map(|(j, e)| e.data.as_ref().map(|data| (Slot(j as u32), data)))
+                .collect::<Vec<_>>();
+
+            f(Slot(i as u32), center, &neighbors);
+        }
+    }
+
+    /// Returns an iterator of `(Slot, &T)`
+    pub fn enumerate_items(&self) -> impl Iterator<Item = (Slot, &T)> {
+        self.entries.iter().enumerate().filter_map(|(i, entry)| {
+            if let Some(data) = &entry.data {
                 Some((Slot(i as u32), data))
             } else {
                 None
@@ -419,6 +2628,25 @@ impl<T> Pool<T> {
         })
     }
 
+    /// Returns an iterator of `(Slot, generation, &T)`
+    ///
+    /// Comparing generations between snapshots tells which slots were reused.
+    pub fn iter_gen(&self) -> impl Iterator<Item = (Slot, u32, &T)> {
+        self.entries.iter().enumerate().filter_map(|(i, entry)| {
+            let data = entry.data.as_ref()?;
+            Some((Slot(i as u32), entry.gen.get(), data))
+        })
+    }
+
+    /// Returns an iterator of `(Slot, reference count, &T)`. Reference counts are as of the last
+    /// synchronization
+    pub fn iter_ref_counts(&self) -> impl Iterator<Item = (Slot, RefCount, &T)> {
+        self.entries.iter().enumerate().filter_map(|(i, entry)| {
+            let data = entry.data.as_ref()?;
+            Some((Slot(i as u32), entry.ref_count, data))
+        })
+    }
+
     /// Returns an iterator of `(Slot, &mut T)`
     pub fn enumerate_items_mut(&mut self) -> impl Iterator<Item = (Slot, &mut T)> {
         self.entries
@@ -432,4 +2660,28 @@ impl<T> Pool<T> {
                 }
             })
     }
+
+    /// Returns a mutable iterator of valid items with a new strong handle to each
+    ///
+    /// Every yielded handle sends `New`, so it's counted on the next sync like any cloned handle.
+    /// Slot-addressed items (reference count zero) become handle-managed: they're invalidated on
+    /// sync once the yielded handles are dropped.
+    pub fn enumerate_handles_mut(&mut self) -> impl Iterator<Item = (Handle<T>, &mut T)> {
+        let tx = &self.tx;
+        self.entries
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(i, entry)| {
+                let data = entry.data.as_mut()?;
+                let slot = Slot(i as u32);
+                tx.send(Message::New(slot, entry.gen));
+                let handle = Handle {
+                    slot,
+                    gen: entry.gen,
+                    sender: tx.clone(),
+                    _ty: PhantomData,
+                };
+                Some((handle, data))
+            })
+    }
 }
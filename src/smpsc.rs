@@ -31,6 +31,22 @@ impl<T> Receiver<T> {
     pub fn recv(&self) -> Option<T> {
         self.0.borrow_mut().pop_front()
     }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Pops up to `n` items in FIFO order
+    pub fn recv_n(&self, n: usize) -> Vec<T> {
+        let mut queue = self.0.borrow_mut();
+        let n = n.min(queue.len());
+        queue.drain(..n).collect()
+    }
 }
 
 pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
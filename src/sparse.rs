@@ -0,0 +1,211 @@
+//! [`SparsePool`], a [`Pool`] variant backed by a `HashMap` (requires `sparse` feature)
+//!
+//! It trades iteration speed for memory when slots are huge and mostly vacant.
+
+use super::*;
+
+/// Sparse counterpart of [`Pool`] with the same [`Handle`]/[`WeakHandle`] API
+///
+/// Vacant slots take no memory. Generations are shared by all slots (every insertion gets a new
+/// one), so that removed entries need no record to reject stale handles.
+#[derive(Debug)]
+pub struct SparsePool<T> {
+    entries: HashMap<u32, PoolEntry<T>>,
+    /// Generation of the next insertion
+    next_gen: Gen,
+    /// Lowest slot that may be vacant, used by [`SparsePool::add`]
+    next_slot: u32,
+    rx: Receiver<Message>,
+    tx: Sender<Message>,
+}
+
+impl<T> Default for SparsePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SparsePool<T> {
+    pub fn new() -> Self {
+        let (tx, rx) = smpsc::unbounded();
+        Self {
+            entries: HashMap::new(),
+            next_gen: unsafe { Gen::new_unchecked(1) },
+            next_slot: 0,
+            rx,
+            tx,
+        }
+    }
+
+    /// Number of live items
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn take_gen(&mut self) -> Gen {
+        let gen = self.next_gen;
+        self.next_gen = Gen::new(gen.get() + 1).expect("Generation overflow!");
+        gen
+    }
+
+    /// Inserts the item into the lowest free slot and returns a strong [`Handle`] for it
+    pub fn add(&mut self, item: impl Into<T>) -> Handle<T> {
+        while self.entries.contains_key(&self.next_slot) {
+            self.next_slot += 1;
+        }
+        assert!(self.next_slot != Slot::INVALID.0, "Slot overflow!");
+        self.add_at(Slot(self.next_slot), item)
+    }
+
+    /// Inserts the item at the slot and returns a strong [`Handle`] for it. Panics if the slot is
+    /// occupied
+    pub fn add_at(&mut self, slot: Slot, item: impl Into<T>) -> Handle<T> {
+        assert!(slot != Slot::INVALID, "tried to insert at the invalid slot");
+        assert!(
+            !self.entries.contains_key(&slot.0),
+            "tried to insert into an occupied slot"
+        );
+
+        let gen = self.take_gen();
+        self.entries.insert(
+            slot.0,
+            PoolEntry {
+                data: Some(item.into()),
+                gen,
+                // count the initial handle below
+                ref_count: 1,
+            },
+        );
+
+        Handle {
+            slot,
+            gen,
+            sender: self.tx.clone(),
+            _ty: Default::default(),
+        }
+    }
+
+    /// Tries to get a reference from a [`WeakHandle`]
+    pub fn get(&self, weak: &WeakHandle<T>) -> Option<&T> {
+        let entry = self.entries.get(&weak.slot.0)?;
+        if entry.gen == weak.gen {
+            entry.data.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Tries to get a mutable reference from a [`WeakHandle`]
+    pub fn get_mut(&mut self, weak: &WeakHandle<T>) -> Option<&mut T> {
+        let entry = self.entries.get_mut(&weak.slot.0)?;
+        if entry.gen == weak.gen {
+            entry.data.as_mut()
+        } else {
+            None
+        }
+    }
+
+    /// Retruns the item if it's valid
+    pub fn get_by_slot(&self, slot: impl AsSlot) -> Option<&T> {
+        self.entries.get(&slot.as_slot().0)?.data.as_ref()
+    }
+
+    /// Retruns the item if it's valid
+    pub fn get_mut_by_slot(&mut self, slot: impl AsSlot) -> Option<&mut T> {
+        self.entries.get_mut(&slot.as_slot().0)?.data.as_mut()
+    }
+
+    /// Tries to upgrade the weak handle to a strong handle. Fails if it's already removed or if the
+    /// ref count is already zero (same as [`Pool::upgrade`]).
+    pub fn upgrade(&self, weak: &WeakHandle<T>) -> Option<Handle<T>> {
+        let entry = self.entries.get(&weak.slot.0)?;
+        if entry.ref_count == 0 || entry.gen != weak.gen {
+            return None;
+        }
+
+        self.tx.send(Message::New(weak.slot, weak.gen));
+        Some(Handle {
+            slot: weak.slot,
+            gen: weak.gen,
+            sender: self.tx.clone(),
+            _ty: PhantomData,
+        })
+    }
+
+    /// Returns an iterator of `(Slot, &T)` in arbitrary order
+    pub fn enumerate_items(&self) -> impl Iterator<Item = (Slot, &T)> {
+        self.entries
+            .iter()
+            .filter_map(|(slot, e)| e.data.as_ref().map(|data| (Slot(*slot), data)))
+    }
+
+    /// Update reference counts letting user visit item with zero reference counts.
+    pub fn sync_refcounts(&mut self, mut on_zero: impl FnMut(&mut Self, Slot)) {
+        while let Some(mes) = self.rx.recv() {
+            match mes {
+                Message::New(slot, gen) => {
+                    if let Some(e) = self.entries.get_mut(&slot.0).filter(|e| e.gen == gen) {
+                        e.ref_count += 1;
+                    }
+                }
+                Message::Drop(slot, gen) => {
+                    let entry = match self.entries.get_mut(&slot.0) {
+                        Some(e) if e.gen == gen => e,
+                        _ => continue,
+                    };
+                    debug_assert!(entry.ref_count > 0, "reference count underflow");
+                    entry.ref_count = entry.ref_count.saturating_sub(1);
+                    if entry.ref_count == 0 {
+                        on_zero(self, slot);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Updates reference counts and invalidates unreferenced items
+    pub fn sync_refcounts_and_invalidate(&mut self) {
+        self.sync_refcounts(|p, slot| {
+            p.invalidate_unreferenced(slot);
+        })
+    }
+
+    /// Invalidates an entry with zero reference count manually, freeing its memory
+    pub fn invalidate_unreferenced(&mut self, slot: Slot) -> bool {
+        match self.entries.get(&slot.0) {
+            Some(e) => assert!(e.ref_count == 0),
+            None => return false,
+        }
+
+        self.entries.remove(&slot.0);
+        self.next_slot = cmp::min(self.next_slot, slot.0);
+        true
+    }
+}
+
+impl<T> ops::Index<&Handle<T>> for SparsePool<T> {
+    type Output = T;
+    fn index(&self, handle: &Handle<T>) -> &Self::Output {
+        let entry = &self.entries[&handle.slot.0];
+        debug_assert!(entry.gen == handle.gen && entry.ref_count > 0);
+        entry
+            .data
+            .as_ref()
+            .expect("dropped entry found while there's strong at least one handle!")
+    }
+}
+
+impl<T> ops::IndexMut<&Handle<T>> for SparsePool<T> {
+    fn index_mut(&mut self, handle: &Handle<T>) -> &mut Self::Output {
+        let entry = self.entries.get_mut(&handle.slot.0).unwrap();
+        debug_assert!(entry.gen == handle.gen && entry.ref_count > 0);
+        entry
+            .data
+            .as_mut()
+            .expect("dropped entry found while there's strong at least one handle!")
+    }
+}
@@ -9,3 +9,2489 @@ fn size() {
         mem::size_of::<u64>() + mem::size_of::<smpsc::Sender<Message>>(),
     );
 }
+
+#[test]
+fn reinsert() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let _h1 = pool.add(1usize);
+
+    let slot = h0.slot();
+    let old = h0.to_downgraded();
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&old), None);
+
+    let h = pool.reinsert_at(slot, 10);
+    assert_eq!(h.slot(), slot);
+    assert_eq!(pool[&h], 10);
+    // the slot has a fresh generation
+    assert_eq!(pool.get(&old), None);
+    assert_eq!(pool.get(&h.to_downgraded()), Some(&10));
+    assert_eq!(pool.entries[slot.to_usize()].ref_count, 1);
+
+    assert_eq!(
+        pool.try_reinsert_at(slot, 20).err(),
+        Some(PoolError::OccupiedSlot)
+    );
+}
+
+#[test]
+fn total_refcount() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let _h0 = pool.add(0usize);
+    assert_eq!(pool.total_refcount(), 1);
+
+    let h1 = pool.add(1usize);
+    let h1_clone = h1.clone();
+    let h1_clone2 = h1.clone();
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.total_refcount(), 4);
+
+    drop((h1, h1_clone, h1_clone2));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.total_refcount(), 1);
+}
+
+#[test]
+fn sort_by() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let h0 = pool.add(3usize);
+    let h1 = pool.add(9usize);
+    let h2 = pool.add(1usize);
+    let h3 = pool.add(2usize);
+
+    let stale = h0.to_downgraded();
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+
+    let remap = pool.sort_by(|a, b| a.cmp(b));
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(remap.len(), 3);
+    assert_eq!(remap[&h2.slot()], Slot(0));
+    assert_eq!(remap[&h3.slot()], Slot(1));
+    assert_eq!(remap[&h0.slot()], Slot(2));
+
+    // the slot now holds another item
+    assert_eq!(pool.get(&stale), None);
+    assert_eq!(pool.get_by_slot(Slot(2)), Some(&3));
+}
+
+#[test]
+fn sort_by_handled_items() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let h0 = pool.add(1usize);
+    let h1 = pool.add(3usize);
+    let h2 = pool.add(2usize);
+
+    // 1 stays at slot 0, and 3 and 2 swap their slots
+    pool.sort_by(|a, b| a.cmp(b));
+    assert_eq!(pool.entries[0].ref_count, 1);
+    assert_eq!(pool.entries[1].ref_count, 0);
+    assert_eq!(pool.entries[2].ref_count, 0);
+
+    // drops from the invalidated handles don't touch the new occupants
+    drop((h1, h2));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    // the handle of the item that stayed still counts
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 3]);
+    assert_eq!(pool.live_count(), 2);
+    assert_eq!(pool.items_with_handles(), 0);
+}
+
+#[test]
+fn is_alive_eventual() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+    assert!(pool.is_alive_eventual(&weak));
+
+    drop(h);
+    // the drop is still queued
+    assert!(pool.get(&weak).is_some());
+    assert!(!pool.is_alive_eventual(&weak));
+}
+
+#[test]
+fn get_or_insert_default() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(5usize);
+
+    // present
+    *pool.get_or_insert_default(h.slot()) += 1;
+    assert_eq!(pool[&h], 6);
+
+    // absent: entries grow up to the slot
+    *pool.get_or_insert_default(Slot(3)) += 2;
+    assert_eq!(pool.get_by_slot(Slot(3)), Some(&2));
+    assert_eq!(pool.get_by_slot(Slot(2)), None);
+    assert_eq!(pool.entries.len(), 4);
+
+    // slot-addressed items survive sync
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get_by_slot(Slot(3)), Some(&2));
+}
+
+#[test]
+fn errors() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+
+    // GenerationOverflow
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+    pool.entries[1].gen = Gen::new(u32::MAX).unwrap();
+    assert_eq!(
+        pool.try_add(2usize).unwrap_err(),
+        PoolError::GenerationOverflow
+    );
+
+    // SlotOutOfBounds
+    assert_eq!(
+        pool.try_invalidate(Slot(5)),
+        Err(PoolError::SlotOutOfBounds)
+    );
+    assert_eq!(
+        pool.try_get2_mut_by_slot(Slot(0), Slot(5)).unwrap_err(),
+        PoolError::SlotOutOfBounds
+    );
+
+    // VacantSlot
+    assert_eq!(pool.try_invalidate(Slot(1)), Err(PoolError::VacantSlot));
+    assert_eq!(
+        pool.try_get2_mut_by_slot(Slot(0), Slot(1)).unwrap_err(),
+        PoolError::VacantSlot
+    );
+
+    // DuplicateSlot
+    assert_eq!(
+        pool.try_get2_mut_by_slot(Slot(0), Slot(0)).unwrap_err(),
+        PoolError::DuplicateSlot
+    );
+
+    // Referenced
+    assert_eq!(pool.try_invalidate(h0.slot()), Err(PoolError::Referenced));
+    assert_eq!(pool.try_index(&h0), Ok(&0));
+
+    // StaleGeneration
+    pool.entries[0].ref_count = 0;
+    assert_eq!(pool.try_invalidate(h0.slot()), Ok(()));
+    pool.entries[0].bump_gen();
+    assert_eq!(pool.try_index(&h0), Err(PoolError::StaleGeneration));
+}
+
+#[test]
+fn refcount_underflow() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let h = pool.add(0usize);
+    let (slot, gen) = (h.slot, h.gen);
+    drop(h);
+
+    // stray drop message
+    pool.tx.send(Message::Drop(slot, gen));
+
+    let mut n_zeros = 0;
+    let res = pool.try_sync_refcounts(|_, _| n_zeros += 1);
+    assert_eq!(res, Err(PoolError::RefCountUnderflow));
+    assert_eq!(n_zeros, 1);
+    assert_eq!(pool.entries[0].ref_count, 0);
+}
+
+#[test]
+fn stale_messages() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let old = pool.add(0usize);
+    let old_clone = old.clone();
+
+    // force the slot to be reused while `old` is alive
+    pool.sync_refcounts_and_invalidate();
+    pool.entries[0].ref_count = 0;
+    pool.invalidate_unreferenced(old.slot());
+    let new = pool.add(1usize);
+    assert_eq!(new.slot(), old.slot());
+
+    drop((old, old_clone));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.entries[0].ref_count, 1);
+    assert_eq!(pool[&new], 1);
+}
+
+#[test]
+fn shrink_to() {
+    let mut pool = Pool::<usize>::with_capacity(16);
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let weak = hs[3].to_downgraded();
+
+    let mut hs = hs.into_iter();
+    let _h0 = hs.next().unwrap();
+    let h1 = hs.next().unwrap();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+
+    pool.shrink_to(3);
+    assert_eq!(pool.entries.len(), 3);
+    assert!(pool.entries.capacity() >= 3 && pool.entries.capacity() < 16);
+
+    pool.shrink_to_fit();
+    assert_eq!(pool.entries.len(), 2);
+
+    // live items are kept
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+    pool.shrink_to_fit();
+    assert_eq!(pool.entries.len(), 1);
+
+    // removed slots are handed out with a newer generation
+    let _hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!(pool.entries.len(), 4);
+    assert_eq!(pool.get(&weak), None);
+}
+
+#[test]
+fn debug_entries() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    let _h1_clone = h1.clone();
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    let h2 = pool.add(2usize);
+    pool.sync_refcounts_and_invalidate();
+
+    let entries = pool.debug_entries().collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        vec![
+            DebugEntry {
+                slot: Slot(0),
+                gen: 2,
+                present: true,
+                ref_count: 1,
+            },
+            DebugEntry {
+                slot: Slot(1),
+                gen: 1,
+                present: true,
+                ref_count: 2,
+            },
+        ]
+    );
+
+    drop(h2);
+    pool.sync_refcounts_and_invalidate();
+    let e = pool.debug_entries().next().unwrap();
+    assert!(!e.present);
+    assert_eq!(e.ref_count, 0);
+}
+
+#[test]
+fn move_into() {
+    let mut src = Pool::<usize>::with_capacity(3);
+    let mut dst = Pool::<usize>::with_capacity(1);
+    let _d = dst.add(100usize);
+    let hs = (0..3usize).map(|i| src.add(i)).collect::<Vec<_>>();
+    let weak = hs[2].to_downgraded();
+
+    let moved = src.move_into(&mut dst, &[Slot(0), Slot(2)]);
+    assert_eq!(moved.len(), 2);
+    assert_eq!(moved[0].0, Slot(1));
+    assert_eq!(dst[&moved[0].1], 0);
+    assert_eq!(dst[&moved[1].1], 2);
+
+    // vanished from the source
+    assert_eq!(src.iter().copied().collect::<Vec<_>>(), vec![1]);
+    assert_eq!(src.get(&weak), None);
+
+    // stale handles of the source don't affect it
+    drop(hs);
+    src.sync_refcounts_and_invalidate();
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(dst.iter().count(), 3);
+}
+
+#[test]
+fn fragmentation() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    assert_eq!(pool.fragmentation(), 0.0);
+
+    let mut hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!(pool.fragmentation(), 0.0);
+
+    hs.truncate(1);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.fragmentation(), 0.75);
+}
+
+#[test]
+fn first_last() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    assert_eq!(pool.first(), None);
+    assert_eq!(pool.last(), None);
+
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let _h1 = hs.next();
+    let _h2 = hs.next();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+
+    assert_eq!(pool.first(), Some((Slot(1), &1)));
+    assert_eq!(pool.last(), Some((Slot(2), &2)));
+
+    *pool.first_mut().unwrap().1 += 10;
+    *pool.last_mut().unwrap().1 += 20;
+    assert_eq!(pool.get_by_slot(Slot(1)), Some(&11));
+    assert_eq!(pool.get_by_slot(Slot(2)), Some(&22));
+}
+
+#[test]
+fn count_where() {
+    let mut pool = Pool::<usize>::with_capacity(8);
+    let _hs = (0..8usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!(pool.count_where(|x| x % 2 == 0), 4);
+    assert_eq!(pool.count_where(|x| *x > 100), 0);
+}
+
+#[test]
+fn try_as_slice() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    assert_eq!(pool.try_as_slice(), Some(&[][..]));
+
+    // dense, but not sliceable with the current entry layout
+    let h0 = pool.add(0usize);
+    let _h1 = pool.add(1usize);
+    assert_eq!(pool.try_as_slice(), None);
+
+    // sparse
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.try_as_slice(), None);
+}
+
+#[test]
+fn retain_mut() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    for i in 0..4usize {
+        pool.get_or_insert_at(Slot(i as u32), || i);
+    }
+
+    pool.retain_mut(|_slot, count| {
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        *count != 0
+    });
+    assert_eq!(
+        pool.enumerate_items().collect::<Vec<_>>(),
+        vec![(Slot(2), &1), (Slot(3), &2)]
+    );
+
+    pool.retain_mut(|_slot, count| {
+        *count -= 1;
+        *count != 0
+    });
+    assert_eq!(
+        pool.enumerate_items().collect::<Vec<_>>(),
+        vec![(Slot(3), &1)]
+    );
+}
+
+#[test]
+fn as_slot() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+
+    assert_eq!(pool.get_by_slot(h.slot()), Some(&0));
+    assert_eq!(pool.get_by_slot(&h), Some(&0));
+    assert_eq!(pool.get_by_slot(weak), Some(&0));
+
+    *pool.get_mut_by_slot(&h).unwrap() += 1;
+    *pool.get_mut_by_slot(weak).unwrap() += 1;
+    assert_eq!(pool[&h], 2);
+}
+
+#[test]
+fn apply_messages() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    for i in 0..3usize {
+        pool.get_or_insert_at(Slot(i as u32), || i);
+    }
+
+    pool.apply_messages(vec![(Slot(0), 2), (Slot(1), 1), (Slot(2), 3), (Slot(9), 1)]);
+    assert_eq!(pool.total_refcount(), 6);
+
+    pool.apply_messages(vec![(Slot(0), -2), (Slot(1), -5), (Slot(2), -1)]);
+    assert_eq!(pool.get_by_slot(Slot(0)), None);
+    assert_eq!(pool.get_by_slot(Slot(1)), None);
+    assert_eq!(pool.get_by_slot(Slot(2)), Some(&2));
+    assert_eq!(pool.total_refcount(), 2);
+}
+
+#[test]
+fn apply_messages_keep_until_manual() {
+    let mut pool = Pool::<usize>::builder()
+        .on_zero_policy(OnZeroPolicy::KeepUntilManual)
+        .build();
+    pool.get_or_insert_at(Slot(0), || 0);
+
+    pool.apply_messages(vec![(Slot(0), 1)]);
+    pool.apply_messages(vec![(Slot(0), -1)]);
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&0));
+    assert_eq!(pool.drain_zero_ref(), vec![(Slot(0), 0)]);
+}
+
+#[test]
+fn liveness_bits() {
+    let mut a = Pool::<usize>::with_capacity(0);
+    let mut b = Pool::<usize>::with_capacity(0);
+    assert!(a.liveness_bits().is_empty());
+
+    for slot in [0u32, 3, 64, 70].iter() {
+        a.get_or_insert_at(Slot(*slot), || 0);
+        b.get_or_insert_at(Slot(*slot), || 1);
+    }
+    assert_eq!(a.liveness_bits(), vec![0b1001, 0b100_0001]);
+    assert_eq!(a.liveness_bits(), b.liveness_bits());
+
+    b.get_or_insert_at(Slot(1), || 1);
+    assert_ne!(a.liveness_bits(), b.liveness_bits());
+}
+
+#[test]
+fn sync_refcounts_with() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let mut hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    hs.remove(1);
+    hs.remove(0);
+
+    let mut removed = Vec::new();
+    pool.sync_refcounts_with(&mut removed, |pool, removed, slot| {
+        removed.push(*pool.get_by_slot(slot).unwrap());
+        pool.invalidate_unreferenced(slot);
+    });
+    assert_eq!(removed, vec![1, 0]);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2]);
+}
+
+#[test]
+fn entry() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+
+    // vacant
+    assert_eq!(*pool.entry(Slot(2)).or_insert(10), 10);
+    assert_eq!(*pool.entry(Slot(0)).or_insert_with(|| 20), 20);
+
+    // occupied
+    assert_eq!(*pool.entry(Slot(2)).or_insert(30), 10);
+
+    pool.entry(Slot(2)).and_modify(|x| *x += 1).or_insert(0);
+    pool.entry(Slot(1)).and_modify(|x| *x += 1).or_insert(0);
+    assert_eq!(
+        pool.enumerate_items().collect::<Vec<_>>(),
+        vec![(Slot(0), &20), (Slot(1), &0), (Slot(2), &11)]
+    );
+}
+
+#[test]
+fn items_with_handles() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let h0 = pool.add(0usize);
+    let _h0_clone = h0.clone();
+    let _h1 = pool.add(1usize);
+    pool.get_or_insert_at(Slot(2), || 2);
+    pool.sync_refcounts_and_invalidate();
+
+    assert_eq!(pool.items_with_handles(), 2);
+    assert_eq!(pool.total_refcount(), 3);
+    assert_eq!(pool.iter().count(), 3);
+}
+
+#[test]
+fn get_all() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut weaks = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+
+    let mut hs = hs.into_iter();
+    let _h0 = hs.next();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+    let _h1 = pool.add(10usize);
+
+    // out of bounds
+    pool.shrink_to_fit();
+    weaks.push(weaks[2]);
+
+    assert_eq!(pool.get_all(&weaks).copied().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn outstanding_handles_on_drop() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    drop(h0);
+    assert_eq!(pool.count_outstanding_handles(), 1);
+    assert_eq!(
+        pool.outstanding_handles_warning().as_deref(),
+        Some("toy_pool: pool dropped while 1 item(s) are referred to by strong handles")
+    );
+
+    // warns, but doesn't panic
+    drop(pool);
+    drop(h1);
+
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let h = pool.add(0usize);
+    drop(h);
+    assert_eq!(pool.outstanding_handles_warning(), None);
+}
+
+#[test]
+fn for_each_with_neighbors_mut() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [0u32, 1, 3, 4].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+
+    let mut visited = Vec::new();
+    pool.for_each_with_neighbors_mut(1, |slot, item, neighbors| {
+        let ns = neighbors.iter().map(|(s, _)| s.0).collect::<Vec<_>>();
+        visited.push((slot.0, ns));
+        *item += neighbors.iter().map(|(_, x)| **x).sum::<usize>();
+    });
+
+    assert_eq!(
+        visited,
+        vec![(0, vec![1]), (1, vec![0]), (3, vec![4]), (4, vec![3])]
+    );
+    // neighbors are seen after the preceding items are updated
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 2, 7, 11]);
+}
+
+#[test]
+fn slot_arithmetic() {
+    let max = Slot(u32::MAX);
+    assert_eq!(Slot(1).checked_add(2), Some(Slot(3)));
+    assert_eq!(max.checked_add(1), None);
+    assert_eq!(Slot(0).checked_sub(1), None);
+    assert_eq!(Slot(3).checked_sub(3), Some(Slot(0)));
+
+    assert_eq!(max.saturating_add(1), max);
+    assert_eq!(Slot(0).saturating_sub(1), Slot(0));
+
+    assert_eq!(Slot(1) + 2, Slot(3));
+    assert_eq!(Slot(3) - 2, Slot(1));
+}
+
+#[test]
+#[should_panic]
+fn slot_add_overflow() {
+    let _ = Slot(u32::MAX) + 1;
+}
+
+#[test]
+fn into_parts_from_parts() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let h1 = hs.next().unwrap();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+    let _h0 = pool.add(10usize);
+
+    let weak = h1.to_downgraded();
+    let parts = pool.into_parts();
+    assert_eq!(
+        parts,
+        vec![
+            PoolEntrySnapshot {
+                data: Some(10),
+                gen: 2,
+                ref_count: 1,
+            },
+            PoolEntrySnapshot {
+                data: Some(1),
+                gen: 1,
+                ref_count: 1,
+            },
+            PoolEntrySnapshot {
+                data: None,
+                gen: 1,
+                ref_count: 0,
+            },
+        ]
+    );
+
+    let pool = Pool::from_parts(parts);
+    assert_eq!(pool.get(&weak), Some(&1));
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&10));
+    assert_eq!(
+        pool.debug_entries().map(|e| e.gen).collect::<Vec<_>>(),
+        vec![2, 1, 1]
+    );
+}
+
+#[test]
+fn iter_non_static() {
+    let strings = [String::from("a"), String::from("b")];
+    let mut pool = Pool::<&str>::with_capacity(2);
+    let _hs = strings
+        .iter()
+        .map(|s| pool.add(s.as_str()))
+        .collect::<Vec<_>>();
+
+    for s in pool.iter_mut() {
+        *s = &s[..0];
+    }
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec!["", ""]);
+    assert_eq!((&pool).into_iter().count(), 2);
+}
+
+#[test]
+fn append() {
+    let mut a = Pool::<usize>::with_capacity(2);
+    let a0 = a.add(0usize);
+    let _a1 = a.add(1usize);
+    drop(a0);
+    a.sync_refcounts_and_invalidate();
+
+    let mut b = Pool::<usize>::with_capacity(3);
+    for slot in [0u32, 2].iter() {
+        b.get_or_insert_at(Slot(*slot), || 10 + *slot as usize);
+    }
+
+    let remap = a.append(b);
+    assert_eq!(remap.len(), 2);
+    assert_eq!(remap[&Slot(0)], Slot(0));
+    assert_eq!(remap[&Slot(2)], Slot(2));
+    assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![10, 1, 12]);
+
+    a.sync_refcounts_and_invalidate();
+    assert_eq!(a.iter().count(), 3);
+}
+
+#[test]
+fn invalid_slot() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let h = pool.add(0usize);
+    assert_ne!(h.slot(), Slot::INVALID);
+
+    assert_eq!(pool.get_by_slot(Slot::INVALID), None);
+    assert_eq!(pool.get_mut_by_slot(Slot::INVALID), None);
+    assert_eq!(
+        pool.try_invalidate(Slot::INVALID),
+        Err(PoolError::SlotOutOfBounds)
+    );
+
+    let weak = WeakHandle::<usize> {
+        slot: Slot::INVALID,
+        gen: h.gen,
+        _ty: PhantomData,
+    };
+    assert_eq!(pool.get(&weak), None);
+    assert!(pool.upgrade(&weak).is_none());
+}
+
+#[test]
+fn iter_gen() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let _h1 = pool.add(1usize);
+    assert_eq!(
+        pool.iter_gen().collect::<Vec<_>>(),
+        vec![(Slot(0), 1, &0), (Slot(1), 1, &1)]
+    );
+
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter_gen().collect::<Vec<_>>(), vec![(Slot(1), 1, &1)]);
+
+    let _h0 = pool.add(2usize);
+    assert_eq!(
+        pool.iter_gen().collect::<Vec<_>>(),
+        vec![(Slot(0), 2, &2), (Slot(1), 1, &1)]
+    );
+}
+
+#[test]
+#[cfg(feature = "sparse")]
+fn sparse_pool() {
+    use crate::sparse::SparsePool;
+
+    let mut pool = SparsePool::<usize>::new();
+    let h0 = pool.add(0usize);
+    let far = pool.add_at(Slot(1_000_000), 1usize);
+    let edge = pool.add_at(Slot(u32::MAX - 1), 2usize);
+    let h1 = pool.add(3usize);
+    assert_eq!(h0.slot(), Slot(0));
+    assert_eq!(h1.slot(), Slot(1));
+    assert_eq!(pool.len(), 4);
+
+    assert_eq!(pool[&far], 1);
+    pool[&edge] += 10;
+    assert_eq!(pool.get_by_slot(Slot(u32::MAX - 1)), Some(&12));
+
+    // same handle semantics as `Pool`
+    let weak = far.to_downgraded();
+    let far2 = pool.upgrade(&weak).unwrap();
+    drop(far);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&weak), Some(&1));
+    drop(far2);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&weak), None);
+    assert_eq!(pool.len(), 3);
+
+    // reuse of a slot never resolves stale handles
+    let reused = pool.add_at(Slot(1_000_000), 4usize);
+    assert_eq!(pool.get(&weak), None);
+    assert_eq!(pool.get(&reused.to_downgraded()), Some(&4));
+
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.add(5usize).slot(), Slot(0));
+}
+
+#[test]
+fn dense_indices() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [1u32, 4, 5, 9].iter() {
+        pool.get_or_insert_at(Slot(*slot), || 0);
+    }
+
+    let (map, slots) = pool.dense_indices();
+    assert_eq!(slots, vec![Slot(1), Slot(4), Slot(5), Slot(9)]);
+    assert_eq!(map.len(), slots.len());
+    for (i, slot) in slots.iter().enumerate() {
+        assert_eq!(map[slot], i as u32);
+    }
+}
+
+#[test]
+fn try_reserve() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let _h = pool.add(0usize);
+    assert!(pool.try_reserve(16).is_ok());
+    assert!(pool.capacity() >= 17);
+    assert!(pool.try_reserve(usize::MAX).is_err());
+}
+
+#[test]
+fn take_unreferenced() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let _h1 = hs.next();
+    drop(hs);
+
+    let slots = pool.take_unreferenced();
+    assert_eq!(slots, vec![Slot(0), Slot(2), Slot(3)]);
+    // not invalidated yet
+    assert_eq!(pool.iter().count(), 4);
+
+    for slot in slots {
+        pool.invalidate_unreferenced(slot);
+    }
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn take_unreferenced_drop_then_upgrade() {
+    let mut pool = Pool::<usize>::with_capacity(2);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    let weak = h0.to_downgraded();
+
+    // the queue holds `Drop` and then `New` for slot 0, twice
+    drop(h0);
+    let h0 = pool.upgrade(&weak).unwrap();
+    drop(h0);
+    let h0 = pool.upgrade(&weak).unwrap();
+    drop(h1);
+
+    assert_eq!(pool.take_unreferenced(), vec![Slot(1)]);
+    assert!(pool.invalidate_unreferenced(Slot(1)));
+    assert!(pool.handle_is_valid(&h0));
+}
+
+#[test]
+fn reserve_exact() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let _hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+
+    pool.reserve_exact(5);
+    assert!(pool.capacity() >= 8);
+
+    pool.reserve(100);
+    assert!(pool.capacity() >= 103);
+}
+
+#[test]
+fn split_at_slot_mut() {
+    let mut pool = Pool::<usize>::with_capacity(4);
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert!(pool.split_at_slot_mut(Slot(4)).is_none());
+
+    let (item, view) = pool.split_at_slot_mut(Slot(1)).unwrap();
+    assert_eq!(view.hole(), Slot(1));
+    assert_eq!(view.get_by_slot(Slot(1)), None);
+    assert_eq!(view.get(&hs[1].to_downgraded()), None);
+    assert_eq!(view.get(&hs[3].to_downgraded()), Some(&3));
+    *item += view.get_by_slot(Slot(0)).unwrap() + view.get_by_slot(Slot(2)).unwrap();
+    *item += view.iter().sum::<usize>();
+
+    assert_eq!(pool[&hs[1]], 1 + 2 + 5);
+}
+
+#[test]
+fn reset_all_vacant_generations() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+
+    // slot 0: live at gen 2, slot 1: vacant at gen 1, slot 2: vacant at gen 3
+    let _h0 = pool.add(10usize);
+    pool.get_or_insert_at(Slot(2), || 0);
+    pool.take_data(2);
+    assert_eq!(
+        pool.debug_entries().map(|e| e.gen).collect::<Vec<_>>(),
+        vec![2, 1, 3]
+    );
+
+    assert_eq!(pool.reset_all_vacant_generations(), 1);
+    assert_eq!(
+        pool.debug_entries().map(|e| e.gen).collect::<Vec<_>>(),
+        vec![2, 1, 1]
+    );
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&10));
+}
+
+#[test]
+fn add_and_get_mut() {
+    let mut pool = Pool::<usize>::with_capacity(1);
+    let (h, item) = pool.add_and_get_mut(1usize);
+    *item += 10;
+    assert_eq!(pool[&h], 11);
+}
+
+#[test]
+fn invalidate_hook() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut pool = Pool::<usize>::with_capacity(3);
+    {
+        let seen = seen.clone();
+        pool.set_invalidate_hook(move |slot, item| seen.borrow_mut().push((slot, *item)));
+    }
+
+    let hs = (0..3usize).map(|i| pool.add(10 + i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let h1 = hs.next().unwrap();
+    let _h2 = hs.next().unwrap();
+
+    pool.sync_refcounts_and_invalidate();
+    assert!(pool.force_invalidate(h1.slot()));
+    assert!(!pool.force_invalidate(h1.slot()));
+    assert_eq!(pool.try_index(&h1), Err(PoolError::StaleGeneration));
+
+    // stale messages from `h1` are ignored
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+
+    assert_eq!(*seen.borrow(), vec![(Slot(0), 10), (Slot(1), 11)]);
+}
+
+#[test]
+fn invalidate_hook_on_removal() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut pool = Pool::<usize>::with_capacity(0);
+    {
+        let seen = seen.clone();
+        pool.set_invalidate_hook(move |slot, item| seen.borrow_mut().push((slot.0, *item)));
+    }
+    for i in 0..6usize {
+        pool.get_or_insert_at(Slot(i as u32), || i);
+    }
+
+    pool.retain_mut(|_, item| *item != 0);
+    pool.drain_while(|_, item| ops::ControlFlow::Continue(*item != 1));
+    {
+        let mut cursor = pool.cursor_mut();
+        cursor.remove_current();
+    }
+    let mut other = Pool::<usize>::with_capacity(0);
+    let _moved = pool.move_into(&mut other, &[Slot(3)]);
+    pool.drain_zero_ref();
+
+    assert_eq!(
+        *seen.borrow(),
+        vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 4), (5, 5)]
+    );
+}
+
+#[test]
+fn clone_from() {
+    let mut src = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| src.add(i)).collect::<Vec<_>>();
+
+    let mut dst = Pool::<usize>::with_capacity(64);
+    let ptr = dst.entries.as_ptr();
+    dst.clone_from(&src);
+    assert_eq!(dst.capacity(), 64);
+    assert_eq!(dst.entries.as_ptr(), ptr);
+    assert_eq!(dst.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(dst.get(&hs[1].to_downgraded()), Some(&1));
+
+    // independent channel
+    drop(hs);
+    src.sync_refcounts_and_invalidate();
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(src.iter().count(), 0);
+    assert_eq!(dst.iter().count(), 3);
+
+    let cloned = dst.clone();
+    assert_eq!(cloned.iter().count(), 3);
+}
+
+#[test]
+fn upgrade_all() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let weaks = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let h1 = hs.next().unwrap();
+    let h2 = hs.next().unwrap();
+    pool.sync_refcounts_and_invalidate();
+
+    let upgraded = pool.upgrade_all(&weaks);
+    assert!(upgraded[0].is_none());
+    assert_eq!(pool[upgraded[1].as_ref().unwrap()], 1);
+    assert_eq!(pool[upgraded[2].as_ref().unwrap()], 2);
+
+    // the upgraded handles are counted
+    drop((h1, h2));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 2);
+    drop(upgraded);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 0);
+}
+
+#[test]
+fn sweep() {
+    let mut pool = Pool::<usize>::with_capacity(5);
+    let hs = (0..5usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let weak4 = hs[4].to_downgraded();
+
+    let mut hs = hs.into_iter();
+    let h0 = hs.next().unwrap();
+    drop(hs.next());
+    let h2 = hs.next().unwrap();
+    drop(hs.next());
+    let h4 = hs.next().unwrap();
+
+    let remap = pool.sweep();
+    assert_eq!(remap.len(), 2);
+    assert_eq!(remap[&h2.slot()], Slot(1));
+    assert_eq!(remap[&h4.slot()], Slot(2));
+
+    // dense
+    assert_eq!(pool.liveness_bits(), vec![0b111_u64]);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![0, 2, 4]);
+
+    // unmoved handles stay valid, moved ones are rejected
+    assert_eq!(pool.try_index(&h0), Ok(&0));
+    assert_eq!(pool.try_index(&h4), Err(PoolError::StaleGeneration));
+    assert_eq!(pool.get(&weak4), None);
+    assert_eq!(pool.get_by_slot(remap[&weak4.slot()]), Some(&4));
+}
+
+#[test]
+fn sweep_handled_items() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    let h0 = hs.next().unwrap();
+    drop(hs.next());
+    let h2 = hs.next().unwrap();
+
+    let remap = pool.sweep();
+    assert_eq!(remap[&h2.slot()], Slot(1));
+    // the moved item has no valid handle
+    assert_eq!(pool.entries[1].ref_count, 0);
+
+    // dropping every handle leaves nothing referenced
+    drop((h0, h2));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.items_with_handles(), 0);
+    assert_eq!(pool.count_outstanding_handles(), 0);
+    // the moved item is slot-addressed and stays until removed manually
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2]);
+    assert!(pool.invalidate_unreferenced(Slot(1)));
+    assert_eq!(pool.live_count(), 0);
+}
+
+#[test]
+fn from_iter() {
+    let mut pool = (0..4usize).collect::<Pool<_>>();
+    assert!(pool.capacity() >= 4);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+    assert_eq!(pool.total_refcount(), 0);
+
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 4);
+}
+
+#[test]
+fn pair_mut() {
+    let mut pool = Pool::<usize>::with_capacity(3);
+    let a = pool.add(1usize);
+    let b = pool.add(2usize);
+    let c = pool.add(3usize);
+
+    match pool.pair_mut(&a, &b) {
+        PairMut::Two(a, b) => mem::swap(a, b),
+        _ => panic!(),
+    }
+    assert_eq!((pool[&a], pool[&b]), (2, 1));
+
+    let a2 = a.clone();
+    match pool.pair_mut(&a, &a2) {
+        PairMut::Same(a) => *a += 10,
+        _ => panic!(),
+    }
+    assert_eq!(pool[&a], 12);
+
+    pool.force_invalidate(c.slot());
+    assert_eq!(pool.pair_mut(&a, &c), PairMut::Dead);
+    assert_eq!(pool.pair_mut(&c, &a), PairMut::Dead);
+}
+
+#[test]
+fn iter_mut_rev() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [0u32, 2, 3].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+
+    let mut order = Vec::new();
+    for (i, item) in pool.iter_mut_rev().enumerate() {
+        order.push(*item);
+        *item = i;
+    }
+    assert_eq!(order, vec![3, 2, 0]);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 1, 0]);
+}
+
+#[test]
+fn swap_data_between_pools() {
+    let mut a = Pool::<usize>::with_capacity(0);
+    let mut b = Pool::<usize>::with_capacity(0);
+    let ha = a.add(1usize);
+    let hb = b.add(2usize);
+    let _hb2 = b.add(3usize);
+
+    assert!(crate::swap_data(&mut a, ha.slot(), &mut b, hb.slot()));
+    assert_eq!(a[&ha], 2);
+    assert_eq!(b[&hb], 1);
+
+    // generations stay with their slots
+    let weak = ha.to_downgraded();
+    assert_eq!(a.get(&weak), Some(&2));
+
+    // vacant or out-of-bounds slots
+    assert!(!crate::swap_data(&mut a, Slot(5), &mut b, hb.slot()));
+    drop(ha);
+    a.sync_refcounts_and_invalidate();
+    assert!(!crate::swap_data(&mut a, Slot(0), &mut b, Slot(1)));
+    assert_eq!(b.get_by_slot(Slot(1)), Some(&3));
+}
+
+#[test]
+fn snapshot_restore() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h0 = pool.add(0usize);
+    let w0 = h0.to_downgraded();
+    let snap = pool.snapshot();
+
+    pool[&h0] = 10;
+    let w1 = pool.add(1usize).downgrade();
+    assert_eq!(pool.get(&w1), Some(&1));
+
+    pool.restore(snap);
+    assert_eq!(pool.get(&w0), Some(&0));
+    assert_eq!(pool.get(&w1), None);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![0]);
+
+    // slots discarded on restore are not reused with the generation of their weak handles
+    let w2 = pool.add(2usize).downgrade();
+    assert_eq!(w2.slot(), w1.slot());
+    assert_eq!(pool.get(&w1), None);
+    assert_eq!(pool.get(&w2), Some(&2));
+}
+
+#[test]
+fn restore_after_shrink() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let _a = pool.add(0usize);
+    let _b = pool.add(1usize);
+    let snap = pool.snapshot();
+
+    // the generation raised by shrinking after the snapshot is kept
+    let c = pool.add(2usize);
+    let wc = c.to_downgraded();
+    drop(c);
+    pool.sync_refcounts_and_invalidate();
+    pool.shrink_to_fit();
+    pool.restore(snap);
+    let d = pool.add(3usize);
+    assert_eq!(d.slot(), wc.slot());
+    assert_eq!(pool.get(&wc), None);
+
+    // generations discarded by restore are kept when the slot is popped and pushed again
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let _a = pool.add(0usize);
+    let b = pool.add(1usize);
+    assert!(pool.force_invalidate(b.slot()));
+    let snap = pool.snapshot();
+    let wc = pool.add(2usize).downgrade();
+    assert_eq!(wc.slot(), b.slot());
+    pool.restore(snap);
+    pool.shrink_to_fit();
+    let d = pool.add(3usize);
+    assert_eq!(d.slot(), wc.slot());
+    assert_eq!(pool.get(&wc), None);
+    assert_eq!(pool[&d], 3);
+}
+
+#[test]
+fn restore_interior_slots() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    pool.get_or_insert_at(Slot(0), || 0);
+    pool.get_or_insert_at(Slot(2), || 2);
+    let snap = pool.snapshot();
+
+    // slot 0 is reused and slot 1 is filled after the snapshot
+    assert!(pool.invalidate_unreferenced(Slot(0)));
+    let w0 = pool.add(10usize).downgrade();
+    let w1 = pool.add(11usize).downgrade();
+    assert_eq!((w0.slot(), w1.slot()), (Slot(0), Slot(1)));
+
+    pool.restore(snap);
+    assert_eq!(pool.get(&w0), None);
+    assert_eq!(pool.get(&w1), None);
+
+    // refilled slots don't hand out the discarded generations again
+    assert!(pool.invalidate_unreferenced(Slot(0)));
+    let h0 = pool.add(20usize);
+    let h1 = pool.add(21usize);
+    assert_eq!((h0.slot(), h1.slot()), (Slot(0), Slot(1)));
+    assert_eq!(pool.get(&w0), None);
+    assert_eq!(pool.get(&w1), None);
+    assert_eq!(pool[&h0], 20);
+    assert_eq!(pool[&h1], 21);
+}
+
+#[test]
+fn enumerate_handles_mut() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+
+    let mut stash = Vec::new();
+    for (handle, item) in pool.enumerate_handles_mut() {
+        *item += 10;
+        if *item % 2 == 0 {
+            stash.push(handle);
+        }
+    }
+
+    // the stashed handles keep their items alive
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![10, 12]);
+    assert_eq!(pool[&stash[1]], 12);
+
+    drop(stash);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 0);
+}
+
+#[test]
+fn builder() {
+    let pool = Pool::<usize>::builder().build();
+    assert_eq!(pool.config(), &PoolConfig::default());
+
+    let mut pool = Pool::<usize>::builder()
+        .capacity(8)
+        .generation_start(5)
+        .on_zero_policy(OnZeroPolicy::KeepUntilManual)
+        .build();
+    assert!(pool.capacity() >= 8);
+    assert_eq!(pool.config().on_zero_policy, OnZeroPolicy::KeepUntilManual);
+
+    let h = pool.add(0usize);
+    assert_eq!(pool.debug_entries().next().unwrap().gen, 5);
+
+    // kept until invalidated manually
+    drop(h);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&0));
+    assert!(pool.invalidate_unreferenced(Slot(0)));
+    assert_eq!(pool.get_by_slot(Slot(0)), None);
+
+    // zero generation falls back to one
+    let mut pool = PoolBuilder::<usize>::new().generation_start(0).build();
+    let _h = pool.add(0usize);
+    assert_eq!(pool.debug_entries().next().unwrap().gen, 1);
+}
+
+#[test]
+fn compact_step() {
+    let build = || {
+        let mut pool = Pool::<usize>::with_capacity(0);
+        for slot in [1u32, 2, 4, 7, 8].iter() {
+            pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+        }
+        pool
+    };
+
+    let mut once = build();
+    let expected = once.compact();
+
+    let mut pool = build();
+    let mut remap = HashMap::new();
+    let mut steps = 0;
+    while !pool.compact_step(2, &mut remap) {
+        steps += 1;
+    }
+    assert_eq!(steps, 2);
+    assert_eq!(remap, expected);
+    assert_eq!(
+        pool.debug_entries().collect::<Vec<_>>(),
+        once.debug_entries().collect::<Vec<_>>()
+    );
+
+    // already compacted
+    assert!(pool.compact_step(0, &mut remap));
+}
+
+#[test]
+fn compact_step_handled_items() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    let h1 = hs.next().unwrap();
+    let h2 = hs.next().unwrap();
+    pool.sync_refcounts_and_invalidate();
+
+    let mut remap = HashMap::new();
+    assert!(!pool.compact_step(1, &mut remap));
+    assert_eq!(remap[&h1.slot()], Slot(0));
+    assert_eq!(pool.entries[0].ref_count, 0);
+
+    // drops from the invalidated handle are ignored, and the unmoved item is still counted
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.live_count(), 2);
+    assert_eq!(pool.items_with_handles(), 1);
+    drop(h2);
+    assert!(pool.compact_step(1, &mut remap));
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.count_outstanding_handles(), 0);
+}
+
+#[test]
+fn get_many() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let ws = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+
+    assert_eq!(
+        pool.get_many([&ws[2], &ws[0], &ws[1]]),
+        [Some(&2), None, Some(&1)]
+    );
+    assert_eq!(pool.get_many::<0>([]), []);
+}
+
+#[test]
+fn content_eq() {
+    let mut a = Pool::<usize>::with_capacity(0);
+    let mut b = Pool::<usize>::with_capacity(0);
+    let _ha = a.add(0usize);
+    let _hb = b.add(0usize);
+    assert!(a.content_eq(&b));
+
+    // trailing vacant entries don't matter
+    let _ = b.get_or_insert_at(Slot(3), || 1);
+    b.force_invalidate(Slot(3));
+    assert!(a.content_eq(&b));
+
+    // different data
+    let _ = a.get_or_insert_at(Slot(1), || 1);
+    let _ = b.get_or_insert_at(Slot(1), || 2);
+    assert!(!a.content_eq(&b));
+    assert!(!a.values_eq(&b));
+
+    // different slots
+    b.force_invalidate(Slot(1));
+    let _ = b.get_or_insert_at(Slot(2), || 1);
+    assert!(!a.content_eq(&b));
+    assert!(a.values_eq(&b));
+}
+
+#[test]
+fn add_returning_slot() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let _h0 = pool.add(0usize);
+    let (h1, slot) = pool.add_returning_slot(1usize);
+    assert_eq!(slot, h1.slot());
+    assert_eq!(pool.get_by_slot(slot), Some(&1));
+}
+
+#[test]
+fn iter_mut_where() {
+    let mut pool = (0..5usize).collect::<Pool<usize>>();
+    for item in pool.iter_mut_where(|x| x % 2 == 0) {
+        *item *= 10;
+    }
+    assert_eq!(
+        pool.iter().copied().collect::<Vec<_>>(),
+        vec![0, 1, 20, 3, 40]
+    );
+}
+
+#[test]
+fn recv_n() {
+    let (tx, rx) = smpsc::unbounded();
+    for i in 0..5 {
+        tx.send(i);
+    }
+    assert_eq!(rx.recv_n(2), vec![0, 1]);
+    assert_eq!(rx.recv_n(10), vec![2, 3, 4]);
+    assert_eq!(rx.recv_n(1), Vec::<i32>::new());
+}
+
+#[test]
+fn keyed_pool() {
+    use crate::keyed::KeyedPool;
+
+    let mut pool = KeyedPool::<&str, usize>::new();
+
+    // miss
+    let a = pool.get_or_spawn("a", || 0);
+    assert_eq!(pool.get(&"a"), Some(&0));
+
+    // hit
+    let a2 = pool.get_or_spawn("a", || 1);
+    assert_eq!(a.slot(), a2.slot());
+    assert_eq!(pool.pool().iter().count(), 1);
+
+    // stale
+    drop(a);
+    drop(a2);
+    let _b = pool.get_or_spawn("b", || 2);
+    pool.pool_mut().sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&"a"), None);
+    assert_eq!(pool.key_count(), 2);
+    pool.prune();
+    assert_eq!(pool.key_count(), 1);
+
+    let a3 = pool.get_or_spawn("a", || 3);
+    assert_eq!(pool.get(&"a"), Some(&3));
+    *pool.get_mut(&"a").unwrap() += 1;
+    assert_eq!(pool.pool()[&a3], 4);
+
+    // handles dropped but not synced yet: the item is respawned rather than upgraded
+    drop(a3);
+    let a4 = pool.get_or_spawn("a", || 5);
+    pool.pool_mut().sync_refcounts_and_invalidate();
+    assert!(pool.pool().handle_is_valid(&a4));
+    assert_eq!(pool.pool()[&a4], 5);
+    assert_eq!(pool.get(&"a"), Some(&5));
+}
+
+#[test]
+fn keyed_pool_keep_until_manual() {
+    use crate::keyed::KeyedPool;
+
+    let mut pool = KeyedPool::<&str, usize>::new();
+    *pool.pool_mut() = Pool::builder()
+        .on_zero_policy(OnZeroPolicy::KeepUntilManual)
+        .build();
+
+    let a = pool.get_or_spawn("a", || 0);
+    drop(a);
+    pool.pool_mut().sync_refcounts_and_invalidate();
+
+    // the kept item is returned rather than duplicated
+    let a = pool.get_or_spawn("a", || 1);
+    assert_eq!(pool.pool()[&a], 0);
+    assert_eq!(pool.pool().live_count(), 1);
+
+    // and the new handle counts
+    pool.pool_mut().sync_refcounts_and_invalidate();
+    assert_eq!(pool.pool().items_with_handles(), 1);
+    assert!(pool.pool_mut().drain_zero_ref().is_empty());
+}
+
+#[test]
+fn sync_reentrant_add() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..2usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    drop(hs);
+
+    // respawn each unreferenced item into (possibly) the same slot
+    let mut respawned = Vec::new();
+    pool.sync_refcounts_with(&mut respawned, |pool, respawned, slot| {
+        let item = *pool.get_by_slot(slot).unwrap();
+        pool.invalidate_unreferenced(slot);
+        respawned.push(pool.add(item + 10));
+    });
+
+    assert_eq!(respawned.len(), 2);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![10, 11]);
+
+    // the respawned items are not processed twice
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 2);
+    drop(respawned);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().count(), 0);
+}
+
+#[test]
+fn weak_for_slot() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+
+    let weaks = pool
+        .slots()
+        .map(|slot| pool.weak_for_slot(slot).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        pool.get_all(&weaks).copied().collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    assert_eq!(pool.weak_for_slot(Slot(0)), None);
+    assert_eq!(pool.weak_for_slot(Slot(3)), None);
+}
+
+#[test]
+fn try_add_no_grow() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    assert_eq!(pool.try_add_no_grow(0).err(), Some(0));
+
+    pool.reserve_exact(2);
+    let cap = pool.capacity();
+    let hs = (0..cap)
+        .map(|i| pool.try_add_no_grow(i).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(pool.try_add_no_grow(cap).err(), Some(cap));
+    assert_eq!(pool.capacity(), cap);
+
+    // vacant slots are reused
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    let h = pool.try_add_no_grow(10).unwrap();
+    assert_eq!(h.slot(), Slot(0));
+    assert_eq!(pool.capacity(), cap);
+}
+
+#[test]
+fn snapshot_slots() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let snap = pool.snapshot_slots();
+    assert_eq!(snap.len(), 3);
+
+    // remove one item and reuse its slot
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    let _h = pool.add(10usize);
+
+    let resolved = snap
+        .iter()
+        .map(|(slot, gen)| pool.get_by_slot_gen(*slot, *gen).copied())
+        .collect::<Vec<_>>();
+    assert_eq!(resolved, vec![None, Some(1), Some(2)]);
+}
+
+#[test]
+fn replace_by_slot() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+
+    assert_eq!(pool.replace_by_slot(h.slot(), 1), Some(0));
+    assert_eq!(pool.get(&weak), Some(&1));
+    assert_eq!(pool.replace_by_slot(Slot(1), 2), None);
+    assert_eq!(pool.get_by_slot(Slot(1)), None);
+}
+
+#[test]
+fn iter_ref_counts() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    let _h1s = (0..2).map(|_| h1.clone()).collect::<Vec<_>>();
+    let _ = pool.get_or_insert_at(Slot(2), || 2);
+    pool.sync_refcounts_and_invalidate();
+
+    let counts = pool
+        .iter_ref_counts()
+        .map(|(slot, count, item)| (slot, count, *item))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        counts,
+        vec![(Slot(0), 1, 0), (Slot(1), 3, 1), (Slot(2), 0, 2)]
+    );
+    drop(h0);
+}
+
+#[test]
+fn handle_leak() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let weak = pool.add(0usize).leak();
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&weak), Some(&0));
+
+    // only the leaked count remains
+    let h = pool.upgrade(&weak).unwrap();
+    drop(h);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&weak), Some(&0));
+
+    assert!(pool.force_invalidate(weak.slot()));
+    assert_eq!(pool.get(&weak), None);
+}
+
+#[test]
+fn live_count() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!((pool.live_count(), pool.vacant_count()), (4, 0));
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!((pool.live_count(), pool.vacant_count()), (2, 2));
+
+    let _ = pool.get_or_insert_at(Slot(6), || 6);
+    assert_eq!((pool.live_count(), pool.vacant_count()), (3, 4));
+
+    pool.retain_mut(|_, x| *x != 2);
+    pool.force_invalidate(Slot(6));
+    let _h = pool.add(10usize);
+    assert_eq!((pool.live_count(), pool.vacant_count()), (2, 5));
+
+    pool.sweep();
+    assert_eq!(pool.live_count(), pool.iter().count());
+    let pool = Pool::from_parts(pool.into_parts());
+    assert_eq!(pool.live_count(), 2);
+}
+
+#[test]
+fn drain_zero_ref() {
+    let mut pool = Pool::<usize>::builder()
+        .on_zero_policy(OnZeroPolicy::KeepUntilManual)
+        .build();
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let weak = hs[1].to_downgraded();
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.live_count(), 3);
+
+    assert_eq!(pool.drain_zero_ref(), vec![(Slot(0), 0), (Slot(1), 1)]);
+    assert_eq!(pool.get(&weak), None);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2]);
+    assert_eq!(pool.drain_zero_ref(), vec![]);
+}
+
+#[test]
+fn observer() {
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Log(Rc<RefCell<Vec<String>>>);
+
+    impl PoolObserver<usize> for Log {
+        fn on_reuse(&mut self, slot: Slot, gen: u32) {
+            self.0
+                .borrow_mut()
+                .push(format!("reuse {:?} {}", slot, gen));
+        }
+
+        fn on_invalidate(&mut self, slot: Slot, item: &usize) {
+            self.0
+                .borrow_mut()
+                .push(format!("invalidate {:?} {}", slot, item));
+        }
+    }
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut pool = Pool::<usize>::with_observer(Log(log.clone()));
+
+    // pushing a new entry is not a reuse
+    let h0 = pool.add(0usize);
+    let _h1 = pool.add(1usize);
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    let _h0 = pool.add(2usize);
+
+    // same for entries pushed by slot-addressed insertion and cursors
+    pool.get_or_insert_at(Slot(2), || 3);
+    {
+        let mut cursor = pool.cursor_mut();
+        cursor.advance();
+        cursor.advance();
+        assert_eq!(cursor.insert_after(4).slot(), Slot(3));
+    }
+    // removals are reported
+    pool.retain_mut(|_, x| *x != 3);
+
+    assert_eq!(
+        *log.borrow(),
+        vec![
+            "invalidate Slot(0) 0".to_string(),
+            "reuse Slot(0) 2".to_string(),
+            "invalidate Slot(2) 3".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn slot_display() {
+    const _: () = assert!(mem::size_of::<Slot>() == mem::size_of::<u32>());
+    const _: () = assert!(mem::align_of::<Slot>() == mem::align_of::<u32>());
+
+    assert_eq!(format!("{}", Slot(7)), "7");
+    assert_eq!(format!("{:>3}", Slot(7)), "  7");
+}
+
+#[test]
+fn into_vec() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [3u32, 0, 5].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+    assert_eq!(pool.into_vec(), vec![0, 3, 5]);
+}
+
+#[test]
+fn get_disjoint_handles_mut() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+
+    let [a, c] = pool.get_disjoint_handles_mut([&hs[0], &hs[2]]).unwrap();
+    mem::swap(a, c);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 1, 0]);
+
+    // duplicate
+    let h0 = hs[0].clone();
+    assert!(pool.get_disjoint_handles_mut([&hs[0], &h0]).is_none());
+
+    // stale
+    pool.force_invalidate(hs[1].slot());
+    assert!(pool.get_disjoint_handles_mut([&hs[0], &hs[1]]).is_none());
+    assert!(pool.get_disjoint_handles_mut([&hs[2]]).is_some());
+}
+
+#[test]
+fn collect_garbage() {
+    use std::{cell::Cell, rc::Rc};
+
+    struct Resource(Rc<Cell<usize>>);
+
+    impl Drop for Resource {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let drops = Rc::new(Cell::new(0));
+    let mut pool = Pool::<Resource>::with_capacity(0);
+    let hs = (0..3)
+        .map(|_| pool.add(Resource(drops.clone())))
+        .collect::<Vec<_>>();
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    drop(hs.next());
+    let batch = pool.collect_garbage();
+
+    assert_eq!(
+        batch.iter().map(|(slot, _)| slot).collect::<Vec<_>>(),
+        vec![Slot(0), Slot(1)]
+    );
+    assert_eq!(pool.live_count(), 1);
+    assert_eq!(drops.get(), 0);
+
+    drop(batch);
+    assert_eq!(drops.get(), 2);
+    assert!(pool.collect_garbage().is_empty());
+}
+
+#[test]
+fn collect_garbage_drop_then_upgrade() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+
+    // the last handle is dropped, but the item is upgraded again before the sync
+    drop(h);
+    let h = pool.upgrade(&weak).unwrap();
+
+    assert!(pool.collect_garbage().is_empty());
+    assert!(pool.handle_is_valid(&h));
+    assert_eq!(pool[&h], 0);
+}
+
+#[test]
+fn auto_shrink() {
+    let mut pool = Pool::<usize>::builder().auto_shrink_fraction(0.4).build();
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let mut hs = hs.into_iter();
+
+    // 1/4 vacant: below the threshold
+    let h0 = hs.next();
+    drop(hs.next_back());
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.entries.len(), 4);
+
+    // 3/4 vacant: trailing vacant entries are removed
+    drop(hs.next_back());
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.entries.len(), 2);
+
+    // sweep shrinks after compaction
+    pool.sweep();
+    assert_eq!(pool.entries.len(), 1);
+
+    // disabled by default
+    let mut pool = Pool::<usize>::with_capacity(0);
+    drop(pool.add(0usize));
+    pool.sweep();
+    assert_eq!(pool.entries.len(), 1);
+}
+
+#[test]
+fn data_ptr_by_slot() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(1usize);
+
+    let ptr = pool.data_ptr_mut_by_slot(h.slot()).unwrap();
+    unsafe { *ptr += 1 };
+    let ptr = pool.data_ptr_by_slot(h.slot()).unwrap();
+    assert_eq!(unsafe { *ptr }, 2);
+
+    assert!(pool.data_ptr_by_slot(Slot(1)).is_none());
+    assert!(pool.data_ptr_mut_by_slot(Slot(1)).is_none());
+}
+
+#[test]
+fn drain_while() {
+    use std::ops::ControlFlow;
+
+    let mut pool = (0..6usize).collect::<Pool<usize>>();
+    let mut removed = 0;
+    pool.drain_while(|_, x| {
+        if removed == 2 {
+            return ControlFlow::Break(());
+        }
+        if *x % 2 == 0 {
+            removed += 1;
+            ControlFlow::Continue(false)
+        } else {
+            ControlFlow::Continue(true)
+        }
+    });
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 3, 4, 5]);
+}
+
+#[test]
+fn generation_overflow_retire() {
+    let mut pool = Pool::<usize>::builder()
+        .generation_overflow(GenerationOverflowPolicy::Retire)
+        .build();
+    let hs = (0..2usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+    pool.entries[0].gen = Gen::new(u32::MAX - 1).unwrap();
+
+    // the last generation is handed out once
+    let h = pool.add(10usize);
+    assert_eq!(h.slot(), Slot(0));
+    let weak = h.to_downgraded();
+    drop(h);
+    pool.sync_refcounts_and_invalidate();
+
+    // then the slot is retired
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert!(hs.iter().all(|h| h.slot() != Slot(0)));
+    assert_eq!(pool.get(&weak), None);
+    assert!(pool.entries[0].is_retired());
+
+    // compaction skips retired slots
+    pool.compact();
+    assert!(pool.entries[0].is_retired());
+
+    // so does sorting
+    pool.sort_by(|a, b| b.cmp(a));
+    assert!(pool.entries[0].is_retired());
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 1, 0]);
+
+    // slot-addressed insertion fails
+    assert_eq!(
+        pool.try_get_or_insert_at(Slot(0), || 10).err(),
+        Some(PoolError::GenerationOverflow)
+    );
+    assert_eq!(
+        pool.try_get_or_insert_at(Slot::INVALID, || 10).err(),
+        Some(PoolError::SlotOutOfBounds)
+    );
+    assert_eq!(
+        pool.try_reinsert_at(Slot(0), 10).err(),
+        Some(PoolError::GenerationOverflow)
+    );
+    assert!(pool.entries[0].is_retired());
+
+    // by default, adding fails
+    let mut pool = Pool::<usize>::with_capacity(0);
+    drop(pool.add(0usize));
+    pool.sync_refcounts_and_invalidate();
+    pool.entries[0].gen = Gen::new(u32::MAX).unwrap();
+    assert_eq!(
+        pool.try_add(1usize).err(),
+        Some(PoolError::GenerationOverflow)
+    );
+}
+
+#[test]
+fn sort_by_exhausted_generation() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    pool.entries[1].gen = Gen::new(u32::MAX).unwrap();
+
+    // the exhausted slot is skipped, and a new entry is pushed for the last item
+    let remap = pool.sort_by(|a, b| b.cmp(a));
+    assert_eq!(remap[&hs[2].slot()], Slot(0));
+    assert_eq!(remap[&hs[1].slot()], Slot(2));
+    assert_eq!(remap[&hs[0].slot()], Slot(3));
+    assert!(pool.entries[1].is_retired());
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 1, 0]);
+    assert_eq!(pool.live_count(), 3);
+}
+
+#[test]
+fn insert_many_at() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    pool.insert_many_at(vec![(Slot(4), 3, 4), (Slot(1), 7, 1)])
+        .unwrap();
+
+    let entries = pool
+        .debug_entries()
+        .map(|e| (e.slot, e.gen, e.present))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        vec![
+            (Slot(0), 1, false),
+            (Slot(1), 7, true),
+            (Slot(2), 1, false),
+            (Slot(3), 1, false),
+            (Slot(4), 3, true),
+        ]
+    );
+    assert_eq!(pool.live_count(), 2);
+
+    // nothing is inserted on error
+    let dup = vec![(Slot(0), 1, 0), (Slot(0), 2, 0)];
+    assert_eq!(pool.insert_many_at(dup), Err(PoolError::DuplicateSlot));
+    let occupied = vec![(Slot(2), 1, 2), (Slot(4), 4, 4)];
+    assert_eq!(pool.insert_many_at(occupied), Err(PoolError::OccupiedSlot));
+    assert_eq!(pool.live_count(), 2);
+
+    // slot-addressed: kept on sync
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 4]);
+}
+
+#[test]
+fn insert_many_at_new_generation() {
+    // a new entry gets the starting generation
+    let mut pool = Pool::<usize>::builder().generation_start(5).build();
+    pool.insert_many_at(vec![(Slot(1), 0, 1)]).unwrap();
+    let gens = pool.debug_entries().map(|e| e.gen).collect::<Vec<_>>();
+    assert_eq!(gens, vec![5, 5]);
+
+    // a vacated slot advances its generation, so that weak handles to the former item stay invalid
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(42usize);
+    let weak = h.to_downgraded();
+    drop(h);
+    pool.sync_refcounts_and_invalidate();
+    pool.insert_many_at(vec![(Slot(0), 0, 0)]).unwrap();
+    assert_eq!(pool.get(&weak), None);
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&0));
+    assert_eq!(pool.debug_entries().next().unwrap().gen, weak.gen.get() + 1);
+
+    // retired slots can't get a new generation
+    pool.drain_zero_ref();
+    pool.entries[0].gen = Gen::new(u32::MAX).unwrap();
+    let retired = vec![(Slot(1), 0, 1), (Slot(0), 0, 0)];
+    assert_eq!(
+        pool.insert_many_at(retired),
+        Err(PoolError::GenerationOverflow)
+    );
+    assert_eq!(pool.live_count(), 0);
+}
+
+#[test]
+fn iter_rev() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [0u32, 2, 5].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+    assert_eq!(pool.iter_rev().copied().collect::<Vec<_>>(), vec![5, 2, 0]);
+}
+
+#[test]
+fn explain_miss() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let ws = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+    assert_eq!(pool.explain_miss(&ws[2]), "slot 2 gen 1 is live");
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(
+        pool.explain_miss(&ws[0]),
+        "slot 0 gen 1 is vacant (item invalidated)"
+    );
+
+    let _h = pool.add(10usize);
+    assert_eq!(
+        pool.explain_miss(&ws[0]),
+        "slot 0 gen mismatch: handle gen 1, current gen 2 (item recycled)"
+    );
+
+    pool.retain_mut(|_, x| *x != 2);
+    assert_eq!(
+        pool.explain_miss(&ws[2]),
+        "slot 2 gen mismatch: handle gen 1, current gen 2 (item removed)"
+    );
+
+    pool.shrink_to_fit();
+    assert_eq!(pool.explain_miss(&ws[2]), "slot 2 out of bounds (len 1)");
+}
+
+#[test]
+fn map_each_mut() {
+    let mut pool = (1..4usize).collect::<Pool<usize>>();
+    let mut slots = Vec::new();
+    pool.map_each_mut(|slot, x| {
+        slots.push(slot);
+        *x *= 2;
+    });
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![2, 4, 6]);
+    assert_eq!(slots, vec![Slot(0), Slot(1), Slot(2)]);
+}
+
+#[test]
+fn any_all() {
+    use std::cell::Cell;
+
+    let pool = (0..5usize).collect::<Pool<usize>>();
+    let visits = Cell::new(0);
+    let pred = |x: &usize| {
+        visits.set(visits.get() + 1);
+        *x == 1
+    };
+
+    assert!(pool.any(pred));
+    assert_eq!(visits.get(), 2);
+
+    visits.set(0);
+    assert!(!pool.all(pred));
+    assert_eq!(visits.get(), 1);
+
+    assert!(pool.all(|x| *x < 5));
+    assert!(!pool.any(|x| *x >= 5));
+    assert!(Pool::<usize>::with_capacity(0).all(|_| false));
+}
+
+#[test]
+fn promote() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [1u32, 3, 4].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+    let weak = pool.weak_for_slot(Slot(4)).unwrap();
+
+    assert_eq!(pool.promote(Slot(4)), Some(Slot(0)));
+    assert_eq!(pool.get(&weak), None);
+    assert_eq!(pool.get_by_slot(Slot(0)), Some(&4));
+    assert_eq!(pool.promote(Slot(3)), Some(Slot(2)));
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![4, 1, 3]);
+
+    // no lower vacant slot, or vacant source
+    assert_eq!(pool.promote(Slot(1)), None);
+    assert_eq!(pool.promote(Slot(3)), None);
+    assert_eq!(pool.promote(Slot(9)), None);
+}
+
+#[test]
+fn promote_handled_item() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+
+    assert_eq!(pool.promote(h1.slot()), Some(Slot(0)));
+    assert_eq!(pool.try_index(&h1), Err(PoolError::StaleGeneration));
+
+    // the stale handle doesn't keep the item referenced
+    drop(h1);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.count_outstanding_handles(), 0);
+    assert!(pool.invalidate_unreferenced(Slot(0)));
+    assert_eq!(pool.live_count(), 0);
+}
+
+#[test]
+fn retain_with() {
+    let mut pool = (0..5usize).collect::<Pool<usize>>();
+    let mut removed = Vec::new();
+    pool.retain_with(&mut removed, |removed, slot, x| {
+        let keep = x % 2 == 1;
+        if !keep {
+            removed.push(slot);
+        }
+        keep
+    });
+    assert_eq!(removed, vec![Slot(0), Slot(2), Slot(4)]);
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 3]);
+}
+
+#[test]
+fn cast() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+
+    let erased: WeakHandle<()> = weak.cast();
+    assert_eq!(erased.slot(), weak.slot());
+    assert_eq!(pool.get(&erased.cast()), Some(&0));
+
+    // the reference count is carried over
+    let h = unsafe { h.cast::<()>().cast::<usize>() };
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool[&h], 0);
+    drop(h);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&weak), None);
+}
+
+#[test]
+fn peek_next_slot() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    assert_eq!(pool.peek_next_slot(), Slot(0));
+    let hs = (0..3usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!(pool.peek_next_slot(), Slot(3));
+
+    let mut hs = hs.into_iter();
+    let _h0 = hs.next();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    let slot = pool.peek_next_slot();
+    assert_eq!(slot, Slot(1));
+    assert_eq!(pool.add(10usize).slot(), slot);
+}
+
+#[test]
+fn split_live_mut() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [0u32, 1, 3, 4].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+
+    let (front, back) = pool.split_live_mut(Slot(2));
+    let back = back.collect::<Vec<_>>();
+    assert_eq!(
+        back.iter().map(|(s, _)| *s).collect::<Vec<_>>(),
+        vec![Slot(3), Slot(4)]
+    );
+    for ((_, src), (_, dst)) in front.zip(back) {
+        *dst += *src;
+        *src = 0;
+    }
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![0, 0, 3, 5]);
+
+    // out of bounds: everything is in the front half
+    let (front, back) = pool.split_live_mut(Slot(10));
+    assert_eq!((front.count(), back.count()), (4, 0));
+}
+
+#[test]
+fn max_generation() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    assert_eq!(pool.max_generation(), 1);
+
+    let _h0 = pool.add(0usize);
+    for i in 0..10usize {
+        drop(pool.add(i));
+        pool.sync_refcounts_and_invalidate();
+    }
+    // slot 1 was filled 10 times
+    assert_eq!(pool.max_generation(), 10);
+}
+
+#[test]
+fn pair_mut_by_weak() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    let ws = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+
+    match pool.pair_mut_by_weak(&ws[0], &ws[1]) {
+        PairResult::Distinct(a, b) => mem::swap(a, b),
+        _ => panic!(),
+    }
+    assert_eq!(pool.get(&ws[0]), Some(&1));
+    assert_eq!(
+        pool.pair_mut_by_weak(&ws[2], &ws[2]),
+        PairResult::Aliased(&mut 2)
+    );
+
+    let mut hs = hs.into_iter();
+    let _h0 = hs.next();
+    let _h1 = hs.next();
+    drop(hs);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.pair_mut_by_weak(&ws[2], &ws[0]), PairResult::FirstDead);
+    assert_eq!(
+        pool.pair_mut_by_weak(&ws[1], &ws[3]),
+        PairResult::SecondDead
+    );
+    assert_eq!(pool.pair_mut_by_weak(&ws[2], &ws[3]), PairResult::BothDead);
+
+    // same slot, different generations
+    let w2 = pool.add(20usize).downgrade();
+    assert_eq!(w2.slot(), ws[2].slot());
+    assert_eq!(pool.pair_mut_by_weak(&ws[2], &w2), PairResult::FirstDead);
+}
+
+#[test]
+fn generation_headroom() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    assert_eq!(pool.generation_headroom(), u32::MAX - 1);
+
+    let _ = pool.get_or_insert_at(Slot(2), || 2);
+    pool.entries[1].gen = Gen::new(u32::MAX - 5).unwrap();
+    assert_eq!(pool.generation_headroom(), 5);
+
+    pool.entries[0].gen = Gen::new(u32::MAX).unwrap();
+    assert_eq!(pool.generation_headroom(), 0);
+    assert_eq!(pool.reset_all_vacant_generations(), 2);
+    // slot 2 was pushed with the first generation
+    assert_eq!(pool.generation_headroom(), u32::MAX - 1);
+}
+
+#[test]
+fn try_dense_view() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    assert_eq!(pool.try_dense_view(), Some((&[][..], vec![])));
+
+    // items are not contiguous even without gaps
+    let h0 = pool.add(0usize);
+    let _h1 = pool.add(1usize);
+    assert_eq!(pool.try_dense_view(), None);
+
+    drop(h0);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.try_dense_view(), None);
+}
+
+#[test]
+fn move_into_redirects_messages() {
+    let mut src = Pool::<usize>::with_capacity(0);
+    let mut dst = Pool::<usize>::with_capacity(0);
+    let _d = dst.add(100usize);
+    let h0 = src.add(0usize);
+    let h1 = src.add(1usize);
+    let h1_clone = h1.clone();
+    src.sync_refcounts_and_invalidate();
+
+    // pending drop before the move
+    drop(h0);
+    let moved = src.move_into(&mut dst, &[Slot(0), Slot(1)]);
+    let (h0_moved, h1_moved) = (&moved[0].1, &moved[1].1);
+
+    // the pending drop applies to the destination: only the returned handle counts
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(dst[h0_moved], 0);
+    let slots = [h0_moved.slot(), h1_moved.slot()];
+    assert_eq!(
+        dst.iter_ref_counts()
+            .filter(|(slot, _, _)| slots.contains(slot))
+            .map(|(_, count, _)| count)
+            .collect::<Vec<_>>(),
+        vec![1, 3]
+    );
+
+    // later drops of handles to the source are forwarded on sync
+    drop(moved);
+    drop(h1);
+    src.sync_refcounts_and_invalidate();
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(dst.iter().copied().collect::<Vec<_>>(), vec![100, 1]);
+    drop(h1_clone);
+    src.sync_refcounts_and_invalidate();
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(dst.iter().copied().collect::<Vec<_>>(), vec![100]);
+    assert!(src.redirects.is_empty());
+}
+
+#[test]
+fn redirect_pending() {
+    let mut src = Pool::<usize>::with_capacity(0);
+    let mut dst = Pool::<usize>::with_capacity(0);
+    let h = src.add(0usize);
+    let gen = src.debug_entries().next().unwrap().gen;
+
+    // reinsert at the same slot and generation in another pool
+    let item = src.take_data(0).unwrap();
+    dst.insert_many_at(vec![(Slot(0), gen, item)]).unwrap();
+    dst.entries[0].ref_count = 1;
+
+    drop(h);
+    src.redirect_pending(&[Slot(0)], &mut dst);
+    assert!(src.rx.recv().is_none());
+    dst.sync_refcounts_and_invalidate();
+    assert_eq!(dst.get_by_slot(Slot(0)), None);
+}
+
+#[test]
+fn cursor_mut() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    for slot in [0u32, 1, 3].iter() {
+        pool.get_or_insert_at(Slot(*slot), || *slot as usize);
+    }
+
+    let mut visited = Vec::new();
+    let mut inserted = None;
+    let mut cursor = pool.cursor_mut();
+    while let Some((slot, item)) = cursor.current() {
+        visited.push((slot, *item));
+        match *item {
+            0 => {
+                // fills the hole at slot 2, visited later
+                inserted = Some(cursor.insert_after(2));
+                cursor.advance();
+            }
+            1 => {
+                assert_eq!(cursor.remove_current(), Some(1));
+            }
+            _ => cursor.advance(),
+        }
+    }
+
+    assert_eq!(
+        visited,
+        vec![(Slot(0), 0), (Slot(1), 1), (Slot(2), 2), (Slot(3), 3)]
+    );
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![0, 2, 3]);
+    assert_eq!(pool[&inserted.unwrap()], 2);
+
+    // inserting after the last item pushes a new entry
+    let mut cursor = pool.cursor_mut();
+    while cursor.current().is_some() {
+        cursor.advance();
+    }
+    let h = cursor.insert_after(4);
+    assert_eq!(h.slot(), Slot(4));
+    assert!(cursor.current().is_none());
+}
+
+#[test]
+fn handle_is_valid() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h0 = pool.add(0usize);
+    let h1 = pool.add(1usize);
+    assert!(pool.handle_is_valid(&h0));
+
+    pool.force_invalidate(h1.slot());
+    assert!(!pool.handle_is_valid(&h1));
+    let _h = pool.add(2usize);
+    assert!(!pool.handle_is_valid(&h1));
+
+    // out of bounds
+    let other = Pool::<usize>::with_capacity(0);
+    assert!(!other.handle_is_valid(&h1));
+}
+
+#[test]
+fn sync_coalesced() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(0usize);
+    let weak = h.to_downgraded();
+    pool.sync_coalesced();
+
+    // drop then re-create before syncing
+    drop(h);
+    let h = pool.upgrade(&weak).unwrap();
+    for _ in 0..3 {
+        drop(h.clone());
+    }
+    pool.sync_coalesced();
+    assert_eq!(pool.iter_ref_counts().next().unwrap().1, 1);
+    assert!(pool.handle_is_valid(&h));
+
+    // the plain sync invalidates the item on the way
+    drop(h);
+    let h = pool.upgrade(&weak).unwrap();
+    pool.sync_refcounts_and_invalidate();
+    assert!(!pool.handle_is_valid(&h));
+
+    // net drops still invalidate
+    let h = pool.add(1usize);
+    drop(h);
+    pool.sync_coalesced();
+    assert_eq!(pool.live_count(), 0);
+}
+
+#[test]
+fn zst_items() {
+    let mut pool = Pool::<()>::with_capacity(0);
+    let hs = (0..3).map(|_| pool.add(())).collect::<Vec<_>>();
+    let ws = hs.iter().map(|h| h.to_downgraded()).collect::<Vec<_>>();
+    assert_eq!(pool.iter().count(), 3);
+    assert_eq!(pool.get(&ws[1]), Some(&()));
+    assert_eq!(pool[&hs[2]], ());
+
+    let mut hs = hs.into_iter();
+    drop(hs.next());
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.get(&ws[0]), None);
+    assert_eq!(pool.live_count(), 2);
+
+    pool.retain_mut(|slot, _| slot != Slot(1));
+    assert_eq!(pool.get(&ws[1]), None);
+    assert_eq!(pool.slots().collect::<Vec<_>>(), vec![Slot(2)]);
+
+    let h = pool.add(());
+    assert_eq!(h.slot(), Slot(0));
+    let [a, b] = pool
+        .get_disjoint_handles_mut([&h, hs.as_slice().last().unwrap()])
+        .unwrap();
+    *a = ();
+    *b = ();
+    assert_eq!(pool.into_vec(), vec![(), ()]);
+}
+
+#[test]
+fn pending_messages() {
+    let mut pool = Pool::<usize>::with_capacity(0);
+    let h = pool.add(0usize);
+    assert_eq!(pool.pending_messages(), 0);
+
+    let hs = (0..3).map(|_| h.clone()).collect::<Vec<_>>();
+    assert_eq!(pool.pending_messages(), 3);
+    drop(hs);
+    assert_eq!(pool.pending_messages(), 6);
+
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.pending_messages(), 0);
+    assert!(pool.rx.is_empty());
+}
+
+#[cfg(feature = "allocator_api")]
+#[test]
+fn custom_allocator() {
+    use std::{
+        alloc::{AllocError, Layout},
+        cell::{Cell, UnsafeCell},
+        ptr::NonNull,
+    };
+
+    /// Bump allocator over a fixed buffer that never frees
+    struct Bump {
+        buf: UnsafeCell<[u64; 256]>,
+        used: Cell<usize>,
+    }
+
+    unsafe impl Allocator for &Bump {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let base = self.buf.get() as *mut u8;
+            let start =
+                (base as usize + self.used.get()).next_multiple_of(layout.align()) - base as usize;
+            let end = start + layout.size();
+            if end > mem::size_of::<[u64; 256]>() {
+                return Err(AllocError);
+            }
+            self.used.set(end);
+            let ptr = NonNull::new(unsafe { base.add(start) }).ok_or(AllocError)?;
+            Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+        }
+
+        unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+    }
+
+    let bump = Bump {
+        buf: UnsafeCell::new([0; 256]),
+        used: Cell::new(0),
+    };
+
+    let mut pool = Pool::<usize, _>::with_capacity_in(4, &bump);
+    let used = bump.used.get();
+    assert!(used > 0);
+    let range = bump.buf.get() as usize..bump.buf.get() as usize + used;
+    assert!(range.contains(&(pool.entries.as_ptr() as usize)));
+
+    let hs = (0..4usize).map(|i| pool.add(i)).collect::<Vec<_>>();
+    assert_eq!(bump.used.get(), used);
+    let (even, _odd): (Vec<_>, Vec<_>) = hs.into_iter().partition(|h| h.slot().0 % 2 == 0);
+    drop(even);
+    pool.sync_refcounts_and_invalidate();
+    assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![1, 3]);
+
+    let mut other = Pool::<usize, _>::new_in(&bump);
+    other.add(10usize);
+    assert!(bump.used.get() > used);
+    assert_eq!(other.into_vec(), vec![10]);
+}